@@ -1,26 +1,59 @@
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use fp_openapi_rust_gen::{Config, ConfigFile, Profile, Target, CONFIG_FILE_NAME};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
     /// Path to input openapi file
-    #[clap(parse(from_os_str), required = true)]
-    pub file: PathBuf,
+    #[clap(parse(from_os_str))]
+    pub file: Option<PathBuf>,
     /// Path to the crate that will be generated
-    #[clap(short, long, parse(from_os_str), required = true)]
-    pub output: PathBuf,
+    #[clap(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Path to a config file containing the values of these flags, type mappings, the operations
+    /// to generate and naming overrides. Flags passed on the command line take precedence.
+    /// Formats without a built-in type, like `email`, `hostname` and `duration`, are generated
+    /// as `String` unless a type is mapped to them in its `[types]` table.
+    /// Defaults to `fp-openapi-gen.toml` in the working directory, if it exists.
+    #[clap(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
 
     /// List of all model paths which should be included.
     /// Every single one will be prefixed with `use ` and included in a private, crate only module.
     /// Multiple values may be passed as multiple args or as one with `;` delimiter.
-    #[clap(long, required = true, value_delimiter = ';')]
+    #[clap(long, value_delimiter = ';')]
     pub models: Vec<String>,
 
+    /// What kind of code should be generated from the OpenAPI document [default: client]
+    #[clap(long, value_enum)]
+    pub target: Option<Target>,
+    /// Which built-in type mappings, dependencies and branding should be used [default: fiberplane]
+    #[clap(long, value_enum)]
+    pub profile: Option<Profile>,
+
     /// Force overwriting of crate path if it exists
     #[clap(short, long)]
     pub force: bool,
 
+    /// Only write the generated modules into the output directory, instead of generating a
+    /// whole crate. The root module is written as `mod.rs` and any other files in the directory
+    /// are left alone.
+    #[clap(long, conflicts_with = "force")]
+    pub module_only: bool,
+    /// Don't write anything, but compare the generated code against the existing output and
+    /// exit with an error if they differ. A diff of all changed files is printed, along with
+    /// missing files and, unless `--module-only` is passed, files that aren't generated.
+    #[clap(long)]
+    pub check: bool,
+
+    /// Path to an existing `Cargo.toml` to add the dependencies of the generated code to.
+    /// Only the dependencies used by the generated code are touched.
+    #[clap(long, parse(from_os_str))]
+    pub manifest_path: Option<PathBuf>,
+
     /// The version string to be included in the crate.
     /// If `local` is `true`, the local dependencies will be referenced with the same version.
     /// Ignored if `workspace` is `true`.
@@ -53,3 +86,141 @@ pub struct Args {
     #[clap(short, long)]
     pub workspace: bool,
 }
+
+/// The options for a single run, after merging the config file and the command line flags.
+pub struct Options {
+    pub file: PathBuf,
+    pub output: PathBuf,
+    pub force: bool,
+    pub module_only: bool,
+    pub check: bool,
+    pub manifest_path: Option<PathBuf>,
+    pub config: Config,
+}
+
+impl Args {
+    pub fn into_options(self) -> Result<Options> {
+        let file = match &self.config {
+            Some(path) => ConfigFile::load(path)?,
+            None if Path::new(CONFIG_FILE_NAME).is_file() => ConfigFile::load(CONFIG_FILE_NAME)?,
+            None => ConfigFile::default(),
+        };
+
+        let output = self
+            .output
+            .or_else(|| file.output.clone())
+            .context("No output path given. Pass `--output` or set `output` in the config file")?;
+
+        // same as `cargo new`, the crate is named after the output directory
+        let name = output
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("Failed to determine crate name from output path")?;
+
+        let mut config = file.to_config(name);
+
+        if let Some(target) = self.target {
+            config.target = target;
+        }
+
+        if let Some(profile) = self.profile {
+            config.profile = profile;
+        }
+
+        if !self.models.is_empty() {
+            config.models = self.models;
+        }
+
+        if config.models.is_empty() {
+            bail!("No models given. Pass `--models` or set `models` in the config file");
+        }
+
+        // like the other flags, these can only be enabled from the command line
+        config.local |= self.local;
+        config.workspace |= self.workspace;
+
+        for (value, flag) in [
+            (&mut config.crate_version, self.crate_version),
+            (&mut config.license, self.license),
+            (&mut config.description, self.description),
+            (&mut config.readme, self.readme),
+            (&mut config.documentation, self.documentation),
+            (&mut config.repository, self.repository),
+        ] {
+            if flag.is_some() {
+                *value = flag;
+            }
+        }
+
+        let module_only = self.module_only || file.module_only;
+        let force = self.force || file.force;
+        let manifest_path = self.manifest_path.or(file.manifest_path);
+
+        if module_only && force {
+            bail!("`force` can't be combined with `module-only`");
+        }
+
+        if manifest_path.is_some() && !module_only {
+            bail!("`manifest-path` can only be used together with `module-only`");
+        }
+
+        Ok(Options {
+            file: self.file.or(file.file).context(
+                "No OpenAPI document given. Pass it as an argument or set `file` in the config file",
+            )?,
+            output,
+            force,
+            module_only,
+            check: self.check,
+            manifest_path,
+            config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn flags_take_precedence_over_the_config_file() {
+        let directory = env::temp_dir().join(format!("fp-openapi-gen-{}-args", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let config = directory.join(CONFIG_FILE_NAME);
+        fs::write(
+            &config,
+            r#"
+file = "openapi.yaml"
+output = "generated/api"
+target = "server"
+models = ["models::*"]
+license = "MIT"
+"#,
+        )
+        .unwrap();
+
+        let options = Args::parse_from([
+            "fp-openapi-rust-gen",
+            "--config",
+            config.to_str().unwrap(),
+            "--target",
+            "cli",
+            "--license",
+            "Apache-2.0",
+        ])
+        .into_options()
+        .unwrap();
+
+        // paths in the file are relative to it
+        assert_eq!(options.file, directory.join("openapi.yaml"));
+        assert_eq!(options.output, directory.join("generated/api"));
+        assert_eq!(options.config.name, "api");
+        assert_eq!(options.config.target, Target::Cli);
+        assert_eq!(options.config.models, ["models::*"]);
+        assert_eq!(options.config.license.as_deref(), Some("Apache-2.0"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+use crate::client_config::default_server_url;
+use crate::codegen::{doc, format_file, ident, parse_type, pascal_case, unique_name};
+use crate::config::QuerySerialization;
+use crate::routes::{generate_models_module, operations, BodyKind, ResponseType, Route};
+use crate::types::TypeMapper;
+use anyhow::Result;
+use okapi::openapi3::{Components, PathItem, Server};
+use okapi::Map;
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(crate) fn generate_cli(
+    paths: &Map<String, PathItem>,
+    servers: &[Server],
+    components: &Components,
+    mapper: &TypeMapper,
+) -> Result<String> {
+    let library = ident(&mapper.config.name.replace('-', "_"));
+    let models = generate_models_module(&mapper.config.models)?;
+
+    // operations without a tag become top level commands
+    let mut groups: BTreeMap<Option<&str>, Vec<Route>> = BTreeMap::new();
+
+    for (endpoint, item) in paths {
+        for (method, operation) in operations(item) {
+            let route = Route::analyze(endpoint, method, operation, item, components, mapper)?;
+            let tag = operation.tags.first().map(String::as_str);
+
+            groups.entry(tag).or_default().push(route);
+        }
+    }
+
+    let args = generate_args(servers, &groups)?;
+
+    let mut command_enums = Vec::new();
+    let (tags, untagged) = top_level_commands(&groups);
+    let (tag_variants, _) = variant_names(&tags, untagged);
+
+    for (tag, variant) in tags.iter().zip(&tag_variants) {
+        command_enums.push(generate_command_enum(
+            &format!("{variant}Command"),
+            &[],
+            &groups[&Some(*tag)],
+        )?);
+    }
+
+    let main = generate_main(&library, &groups)?;
+    let types = mapper.has_types().then(|| quote! { use #library::types; });
+    let helpers = generate_helpers();
+
+    format_file(quote! {
+        #![forbid(unsafe_code)]
+        #![allow(dead_code)]
+        #![allow(deprecated)]
+        #![allow(unused_mut)]
+        #![allow(unused_variables)]
+        #![allow(unused_imports)]
+
+        use anyhow::{Context as _, Result};
+        use clap::{Parser, Subcommand};
+        use #library::builder::ApiClientBuilder;
+        use #library::clients::ApiClient;
+        #types
+        use std::io::Write as _;
+        use std::path::{Path, PathBuf};
+
+        #models
+        #args
+        #(#command_enums)*
+        #main
+        #helpers
+    })
+}
+
+fn generate_args(
+    servers: &[Server],
+    groups: &BTreeMap<Option<&str>, Vec<Route>>,
+) -> Result<TokenStream> {
+    let base_url = match servers.first() {
+        Some(server) => {
+            let url = default_server_url(server);
+
+            quote! { #[clap(long, env = "API_BASE_URL", default_value = #url)] }
+        }
+        None => quote! { #[clap(long, env = "API_BASE_URL")] },
+    };
+
+    let (tags, untagged) = top_level_commands(groups);
+    let command = generate_command_enum("Command", &tags, untagged)?;
+
+    Ok(quote! {
+        #[derive(Parser)]
+        #[clap(author, version, about)]
+        struct Args {
+            /// Base URL of the API
+            #base_url
+            base_url: reqwest::Url,
+
+            /// Token used to authenticate requests
+            #[clap(long, env = "API_TOKEN")]
+            token: Option<String>,
+
+            #[clap(subcommand)]
+            command: Command,
+        }
+
+        #command
+    })
+}
+
+/// Generates a `Subcommand` enum containing a nested subcommand for each tag and a variant for
+/// each route.
+fn generate_command_enum(name: &str, tags: &[&str], routes: &[Route]) -> Result<TokenStream> {
+    let name = ident(name);
+    let (tag_variants, route_variants) = variant_names(tags, routes);
+    let mut variants = Vec::new();
+
+    for (tag, variant) in tags.iter().zip(&tag_variants) {
+        let docs = doc(&format!("{tag} operations"));
+        let command = ident(&format!("{variant}Command"));
+        let variant = ident(variant);
+
+        variants.push(quote! {
+            #docs
+            #variant {
+                #[clap(subcommand)]
+                command: #command,
+            }
+        });
+    }
+
+    for (route, variant) in routes.iter().zip(&route_variants) {
+        let docs = route
+            .operation
+            .summary
+            .as_deref()
+            .or(route.operation.description.as_deref())
+            .map(doc);
+        let variant = ident(variant);
+
+        let mut fields = Vec::new();
+        // flags are named like the parameters in the document, `--body` and `--help` are taken
+        let mut flags: BTreeSet<String> = ["body", "help"]
+            .iter()
+            .map(|&flag| flag.to_owned())
+            .collect();
+
+        for parameter in &route.parameters {
+            let docs = parameter.description.as_deref().map(doc);
+            let ident = ident(&parameter.ident);
+            let type_ = parse_type(&parameter.owned_type)?;
+
+            // parameters in different locations may share a name
+            let flag = if flags.contains(&parameter.name) {
+                unique_name(
+                    format!("{}-{}", parameter.location, parameter.name),
+                    &mut flags,
+                )
+            } else {
+                unique_name(parameter.name.clone(), &mut flags)
+            };
+
+            let value_name = parameter.name.to_uppercase();
+
+            // clap can't parse these from a plain string, so they're passed as JSON instead
+            let clap = if parameter.query == QuerySerialization::Json {
+                quote! {
+                    #[clap(long = #flag, value_name = #value_name, value_parser = parse_json::<#type_>)]
+                }
+            } else {
+                quote! { #[clap(long = #flag, value_name = #value_name)] }
+            };
+
+            fields.push(if parameter.required {
+                quote! { #docs #clap #ident: #type_ }
+            } else {
+                quote! { #docs #clap #ident: Option<#type_> }
+            });
+        }
+
+        if let Some(body) = &route.body {
+            let docs = doc(match body.kind {
+                BodyKind::Json | BodyKind::Form => {
+                    "Path to a JSON or YAML file containing the request body"
+                }
+                BodyKind::Binary => "Path to a file containing the request body",
+            });
+
+            fields.push(quote! {
+                #docs
+                #[clap(long = "body")]
+                body_file: PathBuf
+            });
+        }
+
+        variants.push(quote! {
+            #docs
+            #variant {
+                #(#fields,)*
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[derive(Subcommand)]
+        enum #name {
+            #(#variants,)*
+        }
+    })
+}
+
+fn generate_main(
+    library: &proc_macro2::Ident,
+    groups: &BTreeMap<Option<&str>, Vec<Route>>,
+) -> Result<TokenStream> {
+    let mut arms = Vec::new();
+    let (tags, untagged) = top_level_commands(groups);
+    let (tag_variants, route_variants) = variant_names(&tags, untagged);
+
+    for (tag, variant) in tags.iter().zip(&tag_variants) {
+        let enum_name = ident(&format!("{variant}Command"));
+        let routes = &groups[&Some(*tag)];
+        let (_, nested_variants) = variant_names(&[], routes);
+
+        let nested = routes
+            .iter()
+            .zip(&nested_variants)
+            .map(|(route, nested)| generate_match_arm(library, &enum_name, nested, route))
+            .collect::<Result<Vec<_>>>()?;
+        let variant = ident(variant);
+
+        arms.push(quote! {
+            Command::#variant { command } => match command {
+                #(#nested)*
+            },
+        });
+    }
+
+    for (route, variant) in untagged.iter().zip(&route_variants) {
+        arms.push(generate_match_arm(
+            library,
+            &ident("Command"),
+            variant,
+            route,
+        )?);
+    }
+
+    Ok(quote! {
+        #[tokio::main]
+        async fn main() -> Result<()> {
+            let args = Args::parse();
+            let client = ApiClientBuilder::new(args.base_url)
+                .bearer_token(args.token)
+                .build()?;
+
+            match args.command {
+                #(#arms)*
+            }
+
+            Ok(())
+        }
+    })
+}
+
+fn generate_match_arm(
+    library: &proc_macro2::Ident,
+    enum_name: &proc_macro2::Ident,
+    variant: &str,
+    route: &Route,
+) -> Result<TokenStream> {
+    let variant = ident(variant);
+    let function = ident(&route.name);
+
+    let mut fields: Vec<_> = route
+        .parameters
+        .iter()
+        .map(|parameter| ident(&parameter.ident))
+        .collect();
+
+    if route.body.is_some() {
+        fields.push(ident("body_file"));
+    }
+
+    let mut arguments = vec![quote! { &client }];
+    arguments.extend(route.call_arguments());
+
+    let payload = match &route.body {
+        Some(body) => {
+            let type_ = parse_type(&body.owned_type)?;
+
+            Some(match body.kind {
+                BodyKind::Json | BodyKind::Form => quote! {
+                    let payload: #type_ = read_body(&body_file)?;
+                },
+                BodyKind::Binary => quote! {
+                    let payload: #type_ = std::fs::read(&body_file)
+                        .context("Failed to read body file")?
+                        .into();
+                },
+            })
+        }
+        None => None,
+    };
+
+    let output = match route.response.kind {
+        ResponseType::Json => Some(quote! { print_json(&response)?; }),
+        ResponseType::Text => Some(quote! { println!("{response}"); }),
+        ResponseType::Bytes => Some(quote! { std::io::stdout().write_all(&response)?; }),
+        ResponseType::Headers => Some(quote! { print_headers(&response); }),
+        // items are printed as they arrive, one per line
+        ResponseType::EventStream | ResponseType::JsonLines => Some(quote! {
+            let mut response = response;
+
+            while let Some(item) = futures::StreamExt::next(&mut response).await {
+                let line = serde_json::to_string(&item?)?;
+                println!("{line}");
+            }
+        }),
+        ResponseType::None => None,
+    };
+
+    Ok(quote! {
+        #enum_name::#variant { #(#fields),* } => {
+            #payload
+            let response = #library::#function(#(#arguments),*).await?;
+            #output
+        }
+    })
+}
+
+/// Returns the tags that get their own nested subcommand and the routes without a tag.
+fn top_level_commands<'a, 'b>(
+    groups: &'b BTreeMap<Option<&'a str>, Vec<Route>>,
+) -> (Vec<&'a str>, &'b [Route<'b>]) {
+    let tags = groups.keys().flatten().copied().collect();
+    let untagged = groups.get(&None).map(Vec::as_slice).unwrap_or_default();
+
+    (tags, untagged)
+}
+
+/// Returns the variant names for the given tags and routes, which are unique within the enum.
+fn variant_names(tags: &[&str], routes: &[Route]) -> (Vec<String>, Vec<String>) {
+    let mut taken = BTreeSet::new();
+
+    let tags = tags
+        .iter()
+        .map(|tag| unique_name(pascal_case(tag), &mut taken))
+        .collect();
+    let routes = routes
+        .iter()
+        .map(|route| unique_name(pascal_case(&route.name), &mut taken))
+        .collect();
+
+    (tags, routes)
+}
+
+fn generate_helpers() -> TokenStream {
+    quote! {
+        fn read_body<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+            let contents = std::fs::read_to_string(path).context("Failed to read body file")?;
+
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("yml") | Some("yaml") => {
+                    serde_yaml::from_str(&contents).context("Failed to parse YAML body")
+                }
+                _ => serde_json::from_str(&contents).context("Failed to parse JSON body"),
+            }
+        }
+
+        fn parse_json<T: serde::de::DeserializeOwned>(input: &str) -> Result<T> {
+            serde_json::from_str(input).context("Failed to parse JSON argument")
+        }
+
+        fn print_headers(headers: &reqwest::header::HeaderMap) {
+            for (name, value) in headers {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                println!("{name}: {value}");
+            }
+        }
+
+        fn print_json<T: serde::Serialize>(value: &T) -> Result<()> {
+            let json = serde_json::to_string_pretty(value)?;
+            println!("{json}");
+            Ok(())
+        }
+    }
+}
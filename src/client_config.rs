@@ -1,320 +1,543 @@
-use anyhow::{anyhow, Context, Result};
-use convert_case::{Case, Casing};
+use crate::codegen::{format_file, ident, snake_case, unique_name};
+use crate::config::{Config, Profile};
+use anyhow::{anyhow, bail, Context, Result};
 use okapi::openapi3::Server;
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
-use std::path::Path;
-
-pub(crate) fn generate_client_configs(servers: &[Server], src_path: &Path) -> Result<()> {
-    // https://stackoverflow.com/a/50691004/11494565
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(src_path.join("clients.rs"))
-        .context("Failed to open or create clients.rs file")?;
-
-    let mut writer = BufWriter::new(file);
-
-    writeln!(writer, "use anyhow::{{Context as _, Result}};")?;
-    writeln!(
-        writer,
-        "use reqwest::{{Client, header, Method, RequestBuilder, Url}};"
-    )?;
-    writeln!(writer, "use crate::builder::ApiClientBuilder;")?;
-    writeln!(writer, "use std::time::Duration;\n")?;
-
-    generate_config_method(&mut writer)?;
-
-    for server in servers {
-        generate_client_method(server, &mut writer)?;
-    }
+use proc_macro2::TokenStream;
+use quote::quote;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Generates the `clients` and `builder` modules, in that order.
+pub(crate) fn generate_client_configs(
+    servers: &[Server],
+    config: &Config,
+) -> Result<(String, String)> {
+    let user_agent = default_user_agent(config.profile);
+    let config_method = generate_config_method(&user_agent);
+    let mut taken = BTreeSet::new();
+    let client_methods = servers
+        .iter()
+        .map(|server| generate_client_method(server, &mut taken))
+        .collect::<Result<Vec<_>>>()?;
+    let client_type = generate_client_type();
+
+    let clients = format_file(quote! {
+        use anyhow::{Context as _, Result};
+        use reqwest::{Client, header, Method, RequestBuilder, Response, Url};
+        use super::builder::ApiClientBuilder;
+        use std::time::Duration;
 
-    generate_client_type(&mut writer)?;
+        #config_method
+        #(#client_methods)*
+        #client_type
+    })?;
 
-    writer
-        .flush()
-        .context("Failed to flush output for `clients.rs`")?;
+    let builder = format_file(generate_builder(&user_agent))?;
 
-    // https://stackoverflow.com/a/50691004/11494565
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(src_path.join("builder.rs"))
-        .context("Failed to open or create builder.rs file")?;
+    Ok((clients, builder))
+}
 
-    let mut writer = BufWriter::new(file);
+/// Returns the URL of the server with all variables replaced by their default values.
+pub(crate) fn default_server_url(server: &Server) -> String {
+    let mut url = server.url.clone();
 
-    generate_builder(&mut writer)?;
+    for (name, variable) in &server.variables {
+        url = url.replace(&format!("{{{name}}}"), &variable.default);
+    }
 
-    writer
-        .flush()
-        .context("Failed to flush output for `builder.rs`")
+    url
 }
 
-fn generate_config_method(writer: &mut BufWriter<File>) -> Result<()> {
-    writeln!(writer, "pub fn default_config(")?;
-    writeln!(writer, "    timeout: Option<Duration>,")?;
-    writeln!(writer, "    user_agent: Option<&str>,")?;
-    writeln!(writer, "    default_headers: Option<header::HeaderMap>,")?;
-    writeln!(writer, ") -> Result<Client> {{")?;
-
-    writeln!(
-        writer,
-        "    let mut headers = default_headers.unwrap_or_default();"
-    )?;
-    writeln!(writer, "    headers.insert(header::USER_AGENT, header::HeaderValue::from_str(user_agent.unwrap_or(\"Fiberplane Rust API client\"))?);\n")?;
-
-    writeln!(writer, "    Ok(Client::builder()")?;
-    writeln!(
-        writer,
-        "        .connect_timeout(timeout.unwrap_or_else(|| Duration::from_secs(10)))"
-    )?;
-    writeln!(writer, "        .default_headers(headers)")?;
-    writeln!(writer, "        .build()?)")?;
-
-    writeln!(writer, "}}\n")?;
-
-    Ok(())
+/// The user agent sent when none is configured on the client.
+fn default_user_agent(profile: Profile) -> TokenStream {
+    match profile {
+        Profile::Fiberplane => quote! { "Fiberplane Rust API client" },
+        Profile::Generic => {
+            quote! { concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")) }
+        }
+    }
+}
+
+fn generate_config_method(user_agent: &TokenStream) -> TokenStream {
+    quote! {
+        /// The timeout for connecting to the server, unless another one is configured.
+        pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+        /// Creates a client with the given timeout for connecting to the server, or
+        /// [`DEFAULT_CONNECT_TIMEOUT`] if there is none.
+        pub fn default_config(
+            timeout: Option<Duration>,
+            user_agent: Option<&str>,
+            default_headers: Option<header::HeaderMap>,
+        ) -> Result<Client> {
+            let mut headers = default_headers.unwrap_or_default();
+            headers.insert(
+                header::USER_AGENT,
+                header::HeaderValue::from_str(user_agent.unwrap_or(#user_agent))?,
+            );
+
+            Ok(Client::builder()
+                .connect_timeout(timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+                .default_headers(headers)
+                .build()?)
+        }
+    }
 }
 
-fn generate_client_method(server: &Server, writer: &mut BufWriter<File>) -> Result<()> {
+fn generate_client_method(server: &Server, taken: &mut BTreeSet<String>) -> Result<TokenStream> {
     let description = server
         .description
         .as_ref()
         .ok_or_else(|| anyhow!("Server {:?} does not have `description`", server))?;
     let description = description.replacen("servers", "", 1);
 
-    write!(
-        writer,
-        "pub fn {}_client(",
-        description.to_case(Case::Snake)
-    )?;
+    let name = ident(&unique_name(
+        format!("{}_client", snake_case(&description)).replace("r#", ""),
+        taken,
+    ));
+    let url = &server.url;
 
-    let mut peekable = server.variables.iter().peekable();
+    // `url` and `config` are used as variables in the generated function
+    let mut taken_variables: BTreeSet<String> = ["url", "config"]
+        .iter()
+        .map(|&name| name.to_owned())
+        .collect();
+    let variables: BTreeMap<&str, _> = server
+        .variables
+        .keys()
+        .map(|name| {
+            let ident = ident(&unique_name(snake_case(name), &mut taken_variables));
+            (name.as_str(), ident)
+        })
+        .collect();
+    let idents: Vec<_> = variables.values().collect();
 
-    while let Some((name, _)) = peekable.next() {
-        write!(writer, "\n    {}: Option<&str>,", name.to_case(Case::Snake))?;
+    let url = if server.variables.is_empty() {
+        quote! { let url = #url; }
+    } else {
+        // variable names aren't necessarily valid identifiers, so use positional arguments
+        let regex = Regex::new(r#"\{(.*?)\}"#).context("Failed to build regex")?;
+        let mut values = Vec::new();
 
-        if peekable.peek().is_none() {
-            writeln!(writer)?;
+        for captures in regex.captures_iter(url) {
+            let name = &captures[1];
+
+            match variables.get(name) {
+                Some(ident) => values.push(ident),
+                None => bail!("Server URL {url:?} uses undefined variable `{name}`"),
+            }
         }
-    }
 
-    writeln!(writer, ") -> Result<ApiClient> {{")?;
+        let template = regex.replace_all(url, "{}").into_owned();
+        let defaults = server.variables.values().map(|variable| &variable.default);
 
-    let variables: Vec<String> = server
-        .variables
-        .iter()
-        .map(|(name, server)| {
-            let snake_name = name.to_case(Case::Snake);
-            let default = &server.default;
-            format!("let {snake_name} = {snake_name}.unwrap_or(\"{default}\");")
-        })
-        .collect();
+        quote! {
+            #(let #idents = #idents.unwrap_or(#defaults);)*
+            let url = &format!(#template, #(#values),*);
+        }
+    };
 
-    let variables = variables.join("\n    ");
+    Ok(quote! {
+        pub fn #name(#(#idents: Option<&str>),*) -> Result<ApiClient> {
+            #url
 
-    if !server.variables.is_empty() {
-        writeln!(writer, "    {variables}")?;
+            let config = default_config(Some(Duration::from_secs(30)), None, None)?;
 
-        let format_args: Vec<String> = server
-            .variables
-            .keys()
-            .map(|name| {
-                let snake_name = name.to_case(Case::Snake);
-                format!("{snake_name} = {snake_name}")
+            Ok(ApiClient {
+                client: config,
+                server: Url::parse(url).context("Failed to parse base url from Open API document")?,
+                default_headers: header::HeaderMap::new(),
+                options: RequestOptions::default(),
             })
-            .collect();
-
-        write!(
-            writer,
-            "    let url = &format!(\"{}\", {});",
-            server.url,
-            format_args.join(", ")
-        )?;
-    } else {
-        write!(writer, "    let url = \"{}\";", server.url)?;
-    }
-
-    writeln!(writer, "\n")?;
+        }
+    })
+}
 
-    writeln!(writer, "    let config = default_config(")?;
-    writeln!(writer, "        Some(Duration::from_secs(30)),")?;
-    writeln!(writer, "        None,")?;
-    writeln!(writer, "        None,")?;
-    writeln!(writer, "    )?;\n")?;
+fn generate_client_type() -> TokenStream {
+    quote! {
+        /// Client for the API. Cloning it is cheap, all clones share the same connection pool.
+        #[derive(Clone, Debug)]
+        pub struct ApiClient {
+            pub client: Client,
+            pub server: Url,
+            /// Headers added to every request, for clients that don't have them as default
+            /// headers already
+            pub default_headers: header::HeaderMap,
+            /// Options applied to every request made with the client
+            pub options: RequestOptions,
+        }
 
-    writeln!(writer, "    Ok(ApiClient {{")?;
-    writeln!(writer, "        client: config,")?;
-    writeln!(writer, "        server: Url::parse(url).context(\"Failed to parse base url from Open API document\")?,")?;
-    writeln!(writer, "    }})")?;
+        impl ApiClient {
+            pub fn request(&self, method: Method, endpoint: &str) -> Result<RequestBuilder> {
+                let url = self.server.join(endpoint)?;
+
+                Ok(self.request_url(method, url))
+            }
+
+            /// Creates a request for an operation with its own servers, `base_url` is resolved
+            /// relative to the server of the client.
+            pub fn request_with_base(
+                &self,
+                base_url: &str,
+                method: Method,
+                endpoint: &str,
+            ) -> Result<RequestBuilder> {
+                let mut base_url = self.server.join(base_url)?;
+
+                // keep the path of the base URL, which `join` would replace otherwise
+                if !base_url.path().ends_with('/') {
+                    base_url.set_path(&format!("{}/", base_url.path()));
+                }
+
+                let url = base_url.join(endpoint.trim_start_matches('/'))?;
+
+                Ok(self.request_url(method, url))
+            }
+
+            /// Creates a request for an absolute URL, applying the timeout and headers of the
+            /// request options.
+            pub fn request_url(&self, method: Method, url: Url) -> RequestBuilder {
+                let mut builder = self.client.request(method, url);
+
+                if !self.default_headers.is_empty() {
+                    builder = builder.headers(self.default_headers.clone());
+                }
+
+                if let Some(timeout) = self.options.timeout {
+                    builder = builder.timeout(timeout);
+                }
+
+                if let Some(idempotency_key) = &self.options.idempotency_key {
+                    builder = builder.header("Idempotency-Key", idempotency_key);
+                }
+
+                builder.headers(self.options.headers.clone())
+            }
+
+            /// Sends the request, replacing query parameters with the ones set in the request
+            /// options.
+            pub async fn send(&self, builder: RequestBuilder) -> Result<Response> {
+                let mut request = builder.build()?;
+
+                if !self.options.query.is_empty() {
+                    let overridden =
+                        |name: &str| self.options.query.iter().any(|(query, _)| query == name);
+                    let query: Vec<(String, String)> = request
+                        .url()
+                        .query_pairs()
+                        .filter(|(name, _)| !overridden(name))
+                        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                        .collect();
+
+                    request
+                        .url_mut()
+                        .query_pairs_mut()
+                        .clear()
+                        .extend_pairs(query)
+                        .extend_pairs(&self.options.query);
+                }
+
+                Ok(self.client.execute(request).await?)
+            }
+
+            /// Returns a client sharing the connection pool of this one, which applies the given
+            /// options to all its requests.
+            pub fn with_options(&self, options: RequestOptions) -> ApiClient {
+                ApiClient {
+                    options,
+                    ..self.clone()
+                }
+            }
+
+            pub fn builder(base_url: Url) -> ApiClientBuilder {
+                ApiClientBuilder::new(base_url)
+            }
+        }
 
-    writeln!(writer, "}}\n")?;
+        /// Options for individual requests, see [`ApiClient::with_options`].
+        ///
+        /// Requests can be cancelled by dropping the future or stream returned for them.
+        #[derive(Clone, Debug, Default)]
+        pub struct RequestOptions {
+            timeout: Option<Duration>,
+            headers: header::HeaderMap,
+            idempotency_key: Option<String>,
+            query: Vec<(String, String)>,
+        }
 
-    Ok(())
+        impl RequestOptions {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Set the timeout for the whole request, from connecting until the response body
+            /// has been read.
+            pub fn timeout(mut self, timeout: Duration) -> Self {
+                self.timeout = Some(timeout);
+                self
+            }
+
+            /// Add a header, replacing the default header of the client with the same name.
+            pub fn header(mut self, name: header::HeaderName, value: header::HeaderValue) -> Self {
+                self.headers.insert(name, value);
+                self
+            }
+
+            /// Send an `Idempotency-Key` header, so the server can recognize retried requests.
+            pub fn idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+                self.idempotency_key = Some(idempotency_key.into());
+                self
+            }
+
+            /// Set a query parameter, replacing the value passed to the operation.
+            pub fn query(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+                let name = name.into();
+
+                self.query.retain(|(query, _)| *query != name);
+                self.query.push((name, value.to_string()));
+                self
+            }
+        }
+    }
 }
 
-fn generate_client_type(writer: &mut BufWriter<File>) -> Result<()> {
-    writeln!(writer, "#[derive(Debug)]")?;
-    writeln!(writer, "pub struct ApiClient {{")?;
-    writeln!(writer, "    pub client: Client,")?;
-    writeln!(writer, "    pub server: Url,")?;
-    writeln!(writer, "}}\n")?;
+fn generate_builder(user_agent: &TokenStream) -> TokenStream {
+    quote! {
+        use super::clients::{ApiClient, RequestOptions, DEFAULT_CONNECT_TIMEOUT};
+        use anyhow::Result;
+        use reqwest::{header, Url};
+        use std::time::Duration;
+
+        pub struct ApiClientBuilder {
+            base_url: Url,
+            request_timeout: Option<Duration>,
+            connect_timeout: Option<Duration>,
+            user_agent: Option<String>,
+            bearer_token: Option<String>,
+            proxies: Vec<reqwest::Proxy>,
+            root_certificates: Vec<reqwest::Certificate>,
+            identity: Option<reqwest::Identity>,
+            default_headers: header::HeaderMap,
+            client: Option<reqwest::Client>,
+        }
 
-    writeln!(writer, "impl ApiClient {{")?;
+        impl ApiClientBuilder {
+            pub fn new(base_url: Url) -> Self {
+                Self {
+                    base_url,
+                    request_timeout: None,
+                    connect_timeout: None,
+                    user_agent: None,
+                    bearer_token: None,
+                    proxies: Vec::new(),
+                    root_certificates: Vec::new(),
+                    identity: None,
+                    default_headers: header::HeaderMap::new(),
+                    client: None,
+                }
+            }
+
+            /// Override the base_url for the ApiClient.
+            pub fn base_url(mut self, base_url: Url) -> Self {
+                self.base_url = base_url;
+                self
+            }
+
+            /// Change the timeout for connecting to the server.
+            #[deprecated(note = "use `connect_timeout`, or `request_timeout` to limit whole requests")]
+            pub fn timeout(self, timeout: Option<Duration>) -> Self {
+                self.connect_timeout(timeout)
+            }
+
+            /// Change the timeout for whole requests, from connecting until the response body has
+            /// been read. There is none by default, as it also limits how long streamed responses
+            /// can be read.
+            pub fn request_timeout(mut self, request_timeout: Option<Duration>) -> Self {
+                self.request_timeout = request_timeout;
+                self
+            }
+
+            /// Change the timeout for connecting to the server, which is
+            /// [`DEFAULT_CONNECT_TIMEOUT`] by default.
+            pub fn connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+                self.connect_timeout = connect_timeout;
+                self
+            }
+
+            /// Override the user agent for the ApiClient.
+            pub fn user_agent(mut self, user_agent: Option<impl Into<String>>) -> Self {
+                self.user_agent = user_agent.map(|agent| agent.into());
+                self
+            }
+
+            /// Set an authentication token for the ApiClient.
+            pub fn bearer_token(mut self, bearer_token: Option<impl Into<String>>) -> Self {
+                self.bearer_token = bearer_token.map(|token| token.into());
+                self
+            }
+
+            /// Send requests through a proxy. When called multiple times, the first proxy
+            /// matching the request is used.
+            pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+                self.proxies.push(proxy);
+                self
+            }
+
+            /// Trust an additional root certificate, for example for a server with a self-signed
+            /// certificate.
+            pub fn add_root_certificate(mut self, certificate: reqwest::Certificate) -> Self {
+                self.root_certificates.push(certificate);
+                self
+            }
+
+            /// Set the client certificate presented to the server.
+            pub fn identity(mut self, identity: reqwest::Identity) -> Self {
+                self.identity = Some(identity);
+                self
+            }
+
+            /// Send these headers with every request, in addition to the user agent and the
+            /// authentication token.
+            pub fn with_default_headers(mut self, default_headers: header::HeaderMap) -> Self {
+                self.default_headers = default_headers;
+                self
+            }
+
+            /// Use an existing client instead of building a new one, to share its connection
+            /// pool and configuration. The timeouts, proxies and certificates of the builder don't
+            /// apply to it, the authentication token and default headers are sent with every
+            /// request instead. So is the user agent, but only if it has been set explicitly.
+            pub fn client(mut self, client: reqwest::Client) -> Self {
+                self.client = Some(client);
+                self
+            }
+
+            /// Returns the headers sent with every request. The authentication token is marked as
+            /// sensitive, so it doesn't show up in `Debug` output.
+            pub fn default_headers(&self) -> Result<header::HeaderMap> {
+                let mut headers = self.default_headers.clone();
+
+                // an existing client keeps its own user agent, unless one is set explicitly
+                if self.client.is_none() || self.user_agent.is_some() {
+                    headers.insert(
+                        header::USER_AGENT,
+                        header::HeaderValue::from_str(
+                            self.user_agent
+                                .as_deref()
+                                .unwrap_or(#user_agent),
+                        )?,
+                    );
+                }
+
+                if let Some(bearer) = &self.bearer_token {
+                    let mut value = header::HeaderValue::from_str(&format!("Bearer {}", bearer))?;
+                    // keeps the token out of `Debug` output, for example of the `ApiClient`
+                    value.set_sensitive(true);
+
+                    headers.insert(header::AUTHORIZATION, value);
+                }
+
+                Ok(headers)
+            }
+
+            pub fn build_client(&self) -> Result<reqwest::Client> {
+                let headers = self.default_headers()?;
+
+                let mut builder = reqwest::Client::builder()
+                    .connect_timeout(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+                    .default_headers(headers);
+
+                if let Some(timeout) = self.request_timeout {
+                    builder = builder.timeout(timeout);
+                }
+
+                for proxy in &self.proxies {
+                    builder = builder.proxy(proxy.clone());
+                }
+
+                for certificate in &self.root_certificates {
+                    builder = builder.add_root_certificate(certificate.clone());
+                }
+
+                if let Some(identity) = &self.identity {
+                    builder = builder.identity(identity.clone());
+                }
+
+                Ok(builder.build()?)
+            }
+
+            /// Build the ApiClient.
+            pub fn build(self) -> Result<ApiClient> {
+                let (client, default_headers) = match &self.client {
+                    // an existing client doesn't have the default headers yet
+                    Some(client) => (client.clone(), self.default_headers()?),
+                    None => (self.build_client()?, header::HeaderMap::new()),
+                };
+
+                Ok(ApiClient {
+                    client,
+                    server: self.base_url,
+                    default_headers,
+                    options: RequestOptions::default(),
+                })
+            }
+        }
 
-    writeln!(
-        writer,
-        "    pub fn request(&self, method: Method, endpoint: &str) -> Result<RequestBuilder> {{"
-    )?;
-    writeln!(writer, "        let url = self.server.join(endpoint)?;\n")?;
+        // written out, so the authentication token doesn't end up in logs
+        impl std::fmt::Debug for ApiClientBuilder {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("ApiClientBuilder")
+                    .field("base_url", &self.base_url)
+                    .field("request_timeout", &self.request_timeout)
+                    .field("connect_timeout", &self.connect_timeout)
+                    .field("user_agent", &self.user_agent)
+                    .field("bearer_token", &self.bearer_token.as_ref().map(|_| "Sensitive"))
+                    .field("proxies", &self.proxies)
+                    .field("root_certificates", &self.root_certificates)
+                    .field("identity", &self.identity)
+                    .field("default_headers", &self.default_headers)
+                    .field("client", &self.client)
+                    .finish()
+            }
+        }
+    }
+}
 
-    writeln!(writer, "        Ok(self.client.request(method, url))")?;
-    writeln!(writer, "    }}\n")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    writeln!(
-        writer,
-        "    pub fn builder(base_url: Url) -> ApiClientBuilder {{"
-    )?;
-    writeln!(writer, "        ApiClientBuilder::new(base_url)")?;
-    writeln!(writer, "    }}")?;
+    fn generate(servers: &str) -> (String, String) {
+        let servers: Vec<Server> = serde_yaml::from_str(servers).unwrap();
 
-    writeln!(writer, "}}")?;
+        generate_client_configs(&servers, &Config::new("api")).unwrap()
+    }
 
-    Ok(())
-}
+    #[test]
+    fn timeout_keeps_setting_the_connect_timeout() {
+        let (clients, builder) = generate("[]");
+        let clients = clients.replace(char::is_whitespace, "");
+        let builder = builder.replace(char::is_whitespace, "");
+
+        assert!(clients.contains("DEFAULT_CONNECT_TIMEOUT:Duration=Duration::from_secs(5);"));
+        assert!(clients.contains(".connect_timeout(timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))"));
+        assert!(builder.contains(
+            "pubfntimeout(self,timeout:Option<Duration>)->Self{self.connect_timeout(timeout)}"
+        ));
+        assert!(builder.contains("ifletSome(timeout)=self.request_timeout{"));
+    }
 
-fn generate_builder(writer: &mut BufWriter<File>) -> Result<()> {
-    writeln!(writer, "use crate::clients::ApiClient;")?;
-    writeln!(writer, "use anyhow::Result;")?;
-    writeln!(writer, "use reqwest::{{header, Url}};")?;
-    writeln!(writer, "use std::time::Duration;\n")?;
-
-    writeln!(writer, "#[derive(Debug)]")?;
-    writeln!(writer, "pub struct ApiClientBuilder {{")?;
-    writeln!(writer, "    // Some client specific values")?;
-    writeln!(writer, "    base_url: Url,")?;
-    writeln!(writer, "    timeout: Option<Duration>,\n")?;
-
-    writeln!(
-        writer,
-        "    // These values will be mapped to header values"
-    )?;
-    writeln!(writer, "    user_agent: Option<String>,")?;
-    writeln!(writer, "    bearer_token: Option<String>,")?;
-    writeln!(writer, "}}\n")?;
-
-    writeln!(writer, "impl ApiClientBuilder {{")?;
-    writeln!(writer, "    pub fn new(base_url: Url) -> Self {{")?;
-    writeln!(writer, "        Self {{")?;
-    writeln!(writer, "            base_url,")?;
-    writeln!(writer, "            timeout: None,")?;
-    writeln!(writer, "            user_agent: None,")?;
-    writeln!(writer, "            bearer_token: None,")?;
-    writeln!(writer, "        }}")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(writer, "    /// Override the base_url for the ApiClient.")?;
-    writeln!(
-        writer,
-        "    pub fn base_url(mut self, base_url: Url) -> Self {{"
-    )?;
-    writeln!(writer, "        self.base_url = base_url;")?;
-    writeln!(writer, "        self")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(writer, "    /// Change the timeout for the ApiClient.")?;
-    writeln!(
-        writer,
-        "    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {{"
-    )?;
-    writeln!(writer, "        self.timeout = timeout;")?;
-    writeln!(writer, "        self")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(writer, "    /// Override the user agent for the ApiClient.")?;
-    writeln!(
-        writer,
-        "    pub fn user_agent(mut self, user_agent: Option<impl Into<String>>) -> Self {{"
-    )?;
-    writeln!(
-        writer,
-        "        self.user_agent = user_agent.map(|agent| agent.into());"
-    )?;
-    writeln!(writer, "        self")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(
-        writer,
-        "    /// Set an authentication token for the ApiClient."
-    )?;
-    writeln!(
-        writer,
-        "    pub fn bearer_token(mut self, bearer_token: Option<impl Into<String>>) -> Self {{"
-    )?;
-    writeln!(
-        writer,
-        "        self.bearer_token = bearer_token.map(|token| token.into());"
-    )?;
-    writeln!(writer, "        self")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(
-        writer,
-        "    pub fn build_client(&self) -> Result<reqwest::Client> {{"
-    )?;
-    writeln!(
-        writer,
-        "        let mut headers = header::HeaderMap::new();\n"
-    )?;
-
-    writeln!(writer, "        headers.insert(")?;
-    writeln!(writer, "            header::USER_AGENT,")?;
-    writeln!(writer, "            header::HeaderValue::from_str(")?;
-    writeln!(writer, "                self.user_agent")?;
-    writeln!(writer, "                    .as_deref()")?;
-    writeln!(
-        writer,
-        "                    .unwrap_or(\"Fiberplane Rust API client\"),"
-    )?;
-    writeln!(writer, "            )?,")?;
-    writeln!(writer, "        );\n")?;
-
-    writeln!(
-        writer,
-        "        if let Some(bearer) = &self.bearer_token {{"
-    )?;
-    writeln!(writer, "          headers.insert(")?;
-    writeln!(writer, "              header::AUTHORIZATION,")?;
-    writeln!(writer, "              header::HeaderValue::from_str(")?;
-    writeln!(
-        writer,
-        "                  &format!(\"Bearer {{}}\", bearer)"
-    )?;
-    writeln!(writer, "              )?,")?;
-    writeln!(writer, "          );")?;
-    writeln!(writer, "        }}\n")?;
-
-    writeln!(writer, "        let client = reqwest::Client::builder()")?;
-    writeln!(
-        writer,
-        "            .connect_timeout(self.timeout.unwrap_or_else(|| Duration::from_secs(5)))"
-    )?;
-    writeln!(writer, "            .default_headers(headers)")?;
-    writeln!(writer, "            .build()?;\n")?;
-
-    writeln!(writer, "        Ok(client)")?;
-    writeln!(writer, "    }}\n")?;
-
-    writeln!(writer, "    /// Build the ApiClient.")?;
-    writeln!(writer, "    pub fn build(self) -> Result<ApiClient> {{")?;
-    writeln!(writer, "        let client = self.build_client()?;")?;
-    writeln!(writer, "        let server = self.base_url;")?;
-    writeln!(writer, "        Ok(ApiClient {{ client, server }})")?;
-    writeln!(writer, "    }}")?;
-    writeln!(writer, "}}")?;
-
-    Ok(())
+    #[test]
+    fn default_headers_hide_the_token_and_keep_the_user_agent_of_existing_clients() {
+        let (_, builder) = generate("[]");
+        let builder = builder.replace(char::is_whitespace, "");
+
+        assert!(builder.contains(
+            "ifself.client.is_none()||self.user_agent.is_some(){headers.insert(header::USER_AGENT,"
+        ));
+        assert!(builder
+            .contains("value.set_sensitive(true);headers.insert(header::AUTHORIZATION,value);"));
+        assert!(builder
+            .contains(r#".field("bearer_token",&self.bearer_token.as_ref().map(|_|"Sensitive"))"#));
+    }
 }
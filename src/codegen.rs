@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use check_keyword::CheckKeyword;
+use convert_case::{Case, Casing};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use std::collections::BTreeSet;
+use syn::visit::{self, Visit};
+use syn::{File, Item, Path, Type};
+
+/// Parses the generated tokens as a Rust file and pretty prints it.
+///
+/// Top level items are separated by a blank line, except for consecutive `use` statements and
+/// module declarations.
+pub(crate) fn format_file(tokens: TokenStream) -> Result<String> {
+    let file: File = syn::parse2(tokens).context("Generated code is not valid Rust")?;
+
+    let mut output = prettyplease::unparse(&File {
+        shebang: None,
+        attrs: file.attrs,
+        items: Vec::new(),
+    });
+    let mut previous: Option<&Item> = None;
+
+    for item in &file.items {
+        let grouped = matches!(
+            (previous, item),
+            (Some(Item::Use(_)), Item::Use(_))
+                | (
+                    Some(Item::Mod(syn::ItemMod { content: None, .. })),
+                    Item::Mod(syn::ItemMod { content: None, .. })
+                )
+        );
+
+        if !output.is_empty() && !grouped {
+            output.push('\n');
+        }
+
+        output.push_str(&prettyplease::unparse(&File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: vec![item.clone()],
+        }));
+
+        previous = Some(item);
+    }
+
+    Ok(output)
+}
+
+/// Parses a type that has been mapped from the OpenAPI document.
+pub(crate) fn parse_type(type_: &str) -> Result<Type> {
+    syn::parse_str(type_).with_context(|| format!("Failed to parse `{type_}` as a Rust type"))
+}
+
+pub(crate) fn ident(name: &str) -> Ident {
+    format_ident!("{}", name)
+}
+
+/// Converts a name from the OpenAPI document to a snake cased Rust identifier.
+pub(crate) fn snake_case(name: &str) -> String {
+    escape(convert_case(name, Case::Snake), "unnamed")
+}
+
+/// Converts a name from the OpenAPI document to a pascal cased Rust identifier.
+pub(crate) fn pascal_case(name: &str) -> String {
+    escape(convert_case(name, Case::Pascal), "Unnamed")
+}
+
+fn convert_case(name: &str, case: Case) -> String {
+    // names of operations are raw identifiers already, which are converted without their prefix
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    // characters that can't be part of an identifier separate words instead
+    let words: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    words.to_case(case)
+}
+
+/// Makes sure the converted name is a valid identifier: keywords become raw identifiers (or get
+/// an underscore appended if they can't be raw) and leading digits get an underscore prepended.
+fn escape(name: String, empty: &str) -> String {
+    match name.as_str() {
+        "" => empty.to_owned(),
+        "self" | "Self" | "super" | "crate" => format!("{name}_"),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => format!("_{name}"),
+        _ => name.into_safe(),
+    }
+}
+
+/// Returns `name`, or `name` with a number appended if it's already taken, and marks the result
+/// as taken.
+pub(crate) fn unique_name(name: String, taken: &mut BTreeSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut counter = 1;
+
+    while taken.contains(&unique) {
+        counter += 1;
+        // raw identifiers don't need the prefix anymore once a number is appended
+        unique = format!("{}{counter}", name.trim_start_matches("r#"));
+    }
+
+    taken.insert(unique.clone());
+    unique
+}
+
+/// Creates `#[doc]` attributes for the given text, one per line so they get printed as `///`
+/// comments.
+pub(crate) fn doc(text: &str) -> TokenStream {
+    let lines = text.trim_end().lines().map(|line| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!(" {line}")
+        }
+    });
+
+    quote! {
+        #(#[doc = #lines])*
+    }
+}
+
+/// Returns the first segment of every path with multiple segments in the generated file, which
+/// includes the names of all crates referenced by it.
+pub(crate) fn referenced_crates(source: &str) -> Result<BTreeSet<String>> {
+    struct Visitor(BTreeSet<String>);
+
+    impl<'ast> Visit<'ast> for Visitor {
+        fn visit_path(&mut self, path: &'ast Path) {
+            // single segments are local variables or items in scope
+            if path.segments.len() > 1 {
+                self.0.insert(path.segments[0].ident.to_string());
+            }
+
+            visit::visit_path(self, path);
+        }
+    }
+
+    let file: File = syn::parse_file(source).context("Generated code is not valid Rust")?;
+    let mut visitor = Visitor(BTreeSet::new());
+    visitor.visit_file(&file);
+
+    Ok(visitor.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_converted_to_identifiers() {
+        assert_eq!(snake_case("getUserById"), "get_user_by_id");
+        assert_eq!(snake_case("X-Request-Id"), "x_request_id");
+        assert_eq!(snake_case("page[size]"), "page_size");
+        assert_eq!(pascal_case("list_items"), "ListItems");
+        assert_eq!(pascal_case("item.kind"), "ItemKind");
+        assert_eq!(pascal_case("r#type"), "Type");
+        assert_eq!(snake_case("r#type"), "r#type");
+    }
+
+    #[test]
+    fn invalid_identifiers_are_escaped() {
+        assert_eq!(snake_case("type"), "r#type");
+        assert_eq!(snake_case("async"), "r#async");
+        assert_eq!(pascal_case("self"), "Self_");
+        assert_eq!(snake_case("self"), "self_");
+        assert_eq!(snake_case("super"), "super_");
+        assert_eq!(snake_case("crate"), "crate_");
+        assert_eq!(snake_case("2fa"), "_2_fa");
+        assert_eq!(pascal_case("200"), "_200");
+        assert_eq!(snake_case(""), "unnamed");
+        assert_eq!(pascal_case("$"), "Unnamed");
+    }
+
+    #[test]
+    fn escaped_names_are_valid_identifiers() {
+        for name in ["type", "self", "Self", "super", "crate", "2fa", "", "fn"] {
+            let snake = snake_case(name);
+            let pascal = pascal_case(name);
+
+            syn::parse_str::<Ident>(&snake).unwrap();
+            syn::parse_str::<Ident>(&pascal).unwrap();
+        }
+    }
+
+    #[test]
+    fn unique_names_get_a_number_appended() {
+        let mut taken = BTreeSet::new();
+
+        assert_eq!(unique_name("item".to_owned(), &mut taken), "item");
+        assert_eq!(unique_name("item".to_owned(), &mut taken), "item2");
+        assert_eq!(unique_name("item".to_owned(), &mut taken), "item3");
+        assert_eq!(unique_name("r#type".to_owned(), &mut taken), "r#type");
+        assert_eq!(unique_name("r#type".to_owned(), &mut taken), "type2");
+    }
+}
@@ -0,0 +1,369 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the config file that is picked up from the working directory.
+pub const CONFIG_FILE_NAME: &str = "fp-openapi-gen.toml";
+
+/// Configuration for a single generator run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Name of the generated crate. Also used as the module name when generating from a build
+    /// script (converted to snake case).
+    pub name: String,
+    /// What kind of code should be generated from the OpenAPI document
+    pub target: Target,
+    /// Which built-in type mappings, dependencies and branding are used
+    pub profile: Profile,
+    /// List of all model paths which should be included.
+    /// Every single one will be prefixed with `use ` and included in a private, crate only module.
+    pub models: Vec<String>,
+
+    /// The version string to be included in the crate.
+    /// If `local` is `true`, the local dependencies will be referenced with the same version.
+    /// Ignored if `workspace` is `true`.
+    pub crate_version: Option<String>,
+    /// Optional license string to include in the generated Cargo file.
+    /// Will inherit from workspace if `workspace` is `true`.
+    pub license: Option<String>,
+    /// Optional description string to include in the generated Cargo file.
+    pub description: Option<String>,
+    /// Optional readme path string to include in the generated Cargo file.
+    pub readme: Option<String>,
+    /// Optional documentation url to include in the generated Cargo file.
+    /// Will inherit from workspace if `workspace` is `true`.
+    pub documentation: Option<String>,
+    /// Optional repository url to include in the generated Cargo file.
+    /// Will inherit from workspace if `workspace` is `true`.
+    pub repository: Option<String>,
+
+    /// Whenever fiberplane-rs dependencies are located locally relative to the output crate.
+    /// Ignored if `workspace` is `true`.
+    pub local: bool,
+    /// Set to indicate fiberplane-rs dependencies should be loaded from the workspace
+    pub workspace: bool,
+
+    /// Rust types to use for schemas, keyed by their `format` or `x-rust-type` extension.
+    /// These take precedence over the built-in mappings of the profile. Formats without a
+    /// built-in mapping, like `email`, `hostname` and `duration`, are generated as `String`.
+    pub type_mappings: BTreeMap<String, TypeMapping>,
+    /// If not empty, only the operations with these IDs are generated.
+    pub include_operations: Vec<String>,
+    /// Operations with these IDs are skipped.
+    pub exclude_operations: Vec<String>,
+    /// Names to use for the generated functions instead of the operation IDs, keyed by
+    /// operation ID.
+    pub operation_names: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target: Target::Client,
+            profile: Profile::Fiberplane,
+            models: Vec::new(),
+            crate_version: None,
+            license: None,
+            description: None,
+            readme: None,
+            documentation: None,
+            repository: None,
+            local: false,
+            workspace: false,
+            type_mappings: BTreeMap::new(),
+            include_operations: Vec::new(),
+            exclude_operations: Vec::new(),
+            operation_names: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the mapping for a `format` or `x-rust-type` value, falling back to the built-in
+    /// mappings of the profile.
+    pub(crate) fn type_mapping(&self, key: &str) -> Option<TypeMapping> {
+        self.type_mappings
+            .get(key)
+            .cloned()
+            .or_else(|| self.profile.type_mapping(key))
+    }
+}
+
+/// Maps a schema `format` (or `x-rust-type` extension) to a Rust type.
+///
+/// In the config file this is either just the path of the type, or a table like
+/// `{ type = "chrono::DateTime<chrono::Utc>", query = "to-string" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "TypeMappingEntry")]
+pub struct TypeMapping {
+    /// Path of the Rust type
+    pub rust_type: String,
+    /// How values are serialized when used in a query string
+    pub query: QuerySerialization,
+    /// Whether the generated functions take parameters of this type by reference
+    pub by_reference: bool,
+}
+
+impl TypeMapping {
+    pub fn new(rust_type: impl Into<String>) -> Self {
+        Self {
+            rust_type: rust_type.into(),
+            query: QuerySerialization::Serde,
+            by_reference: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TypeMappingEntry {
+    Path(String),
+    #[serde(rename_all = "kebab-case")]
+    Table {
+        #[serde(rename = "type")]
+        rust_type: String,
+        #[serde(default)]
+        query: QuerySerialization,
+        #[serde(default)]
+        by_reference: bool,
+    },
+}
+
+impl From<TypeMappingEntry> for TypeMapping {
+    fn from(entry: TypeMappingEntry) -> Self {
+        match entry {
+            TypeMappingEntry::Path(rust_type) => Self::new(rust_type),
+            TypeMappingEntry::Table {
+                rust_type,
+                query,
+                by_reference,
+            } => Self {
+                rust_type,
+                query,
+                by_reference,
+            },
+        }
+    }
+}
+
+/// How a value is turned into a query string parameter.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuerySerialization {
+    /// Passed to `reqwest` as is, which serializes it using `serde`
+    #[default]
+    Serde,
+    /// Converted using its `Display` implementation
+    ToString,
+    /// Serialized as a JSON string
+    Json,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Maps the Fiberplane specific formats (`base64uuid`, `name`, `date-time`) to types from
+    /// the fiberplane repository and brands the client as Fiberplane's
+    Fiberplane,
+    /// Only depends on crates from crates.io, so the output builds for any document
+    Generic,
+}
+
+impl Profile {
+    fn type_mapping(self, key: &str) -> Option<TypeMapping> {
+        match self {
+            Profile::Fiberplane => match key {
+                "base64uuid" => Some(TypeMapping::new("base64uuid::Base64Uuid")),
+                "name" => Some(TypeMapping {
+                    by_reference: true,
+                    ..TypeMapping::new("fiberplane_models::names::Name")
+                }),
+                "date" | "date-time" => Some(TypeMapping {
+                    query: QuerySerialization::ToString,
+                    ..TypeMapping::new("fiberplane_models::timestamps::Timestamp")
+                }),
+                "password" => Some(TypeMapping::new("secrecy::SecretString")),
+                _ => None,
+            },
+            Profile::Generic => None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// An API client using `reqwest`
+    Client,
+    /// An `async_trait` with one method per operation and an `axum` router dispatching to it
+    Server,
+    /// An API client together with a `clap` based command line interface calling into it
+    Cli,
+}
+
+/// The contents of a `fp-openapi-gen.toml` file.
+///
+/// Every value is optional. When running the binary, flags passed on the command line take
+/// precedence over the values in the file. Relative paths are resolved against the directory
+/// containing the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    /// Path to the OpenAPI document
+    pub file: Option<PathBuf>,
+    /// Path to the crate (or module) that will be generated
+    pub output: Option<PathBuf>,
+    pub target: Option<Target>,
+    pub profile: Option<Profile>,
+    pub models: Vec<String>,
+
+    pub force: bool,
+    pub module_only: bool,
+    pub manifest_path: Option<PathBuf>,
+
+    pub crate_version: Option<String>,
+    pub license: Option<String>,
+    pub description: Option<String>,
+    pub readme: Option<String>,
+    pub documentation: Option<String>,
+    pub repository: Option<String>,
+    pub local: bool,
+    pub workspace: bool,
+
+    /// Additional type mappings, see [`Config::type_mappings`]
+    pub types: BTreeMap<String, TypeMapping>,
+    pub operations: OperationFilter,
+    /// Maps operation IDs to function names, see [`Config::operation_names`]
+    pub names: BTreeMap<String, String>,
+}
+
+/// The `[operations]` table of the config file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperationFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl ConfigFile {
+    /// Reads and parses the config file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file `{}`", path.display()))?;
+        let mut config: Self = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file `{}`", path.display()))?;
+
+        if let Some(directory) = path.parent() {
+            for path in [
+                &mut config.file,
+                &mut config.output,
+                &mut config.manifest_path,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *path = directory.join(&*path);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Creates the generator config from the values in this file.
+    pub fn to_config(&self, name: impl Into<String>) -> Config {
+        Config {
+            target: self.target.unwrap_or(Target::Client),
+            profile: self.profile.unwrap_or(Profile::Fiberplane),
+            models: self.models.clone(),
+            crate_version: self.crate_version.clone(),
+            license: self.license.clone(),
+            description: self.description.clone(),
+            readme: self.readme.clone(),
+            documentation: self.documentation.clone(),
+            repository: self.repository.clone(),
+            local: self.local,
+            workspace: self.workspace,
+            type_mappings: self.types.clone(),
+            include_operations: self.operations.include.clone(),
+            exclude_operations: self.operations.exclude.clone(),
+            operation_names: self.names.clone(),
+            ..Config::new(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file(contents: &str) -> Result<ConfigFile> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    #[test]
+    fn config_file_values_are_used() {
+        let file = config_file(
+            r#"
+target = "server"
+models = ["models::*"]
+local = true
+
+[types]
+uuid = "uuid::Uuid"
+date-time = { type = "chrono::DateTime<chrono::Utc>", query = "to-string" }
+name = { type = "String", by-reference = true }
+
+[operations]
+exclude = ["pong"]
+
+[names]
+notebook_get = "get_notebook"
+"#,
+        )
+        .unwrap();
+        let config = file.to_config("api");
+
+        assert_eq!(config.name, "api");
+        assert_eq!(config.target, Target::Server);
+        assert_eq!(config.profile, Profile::Fiberplane);
+        assert_eq!(config.models, ["models::*"]);
+        assert!(config.local);
+        assert_eq!(config.exclude_operations, ["pong"]);
+        assert_eq!(config.operation_names["notebook_get"], "get_notebook");
+        assert_eq!(
+            config.type_mappings["date-time"],
+            TypeMapping {
+                query: QuerySerialization::ToString,
+                ..TypeMapping::new("chrono::DateTime<chrono::Utc>")
+            }
+        );
+        assert!(config.type_mappings["name"].by_reference);
+    }
+
+    #[test]
+    fn configured_type_mappings_take_precedence_over_the_profile() {
+        let config = config_file("[types]\ndate-time = \"time::OffsetDateTime\"")
+            .unwrap()
+            .to_config("api");
+
+        assert_eq!(
+            config.type_mapping("date-time"),
+            Some(TypeMapping::new("time::OffsetDateTime"))
+        );
+        assert_eq!(
+            config.type_mapping("base64uuid"),
+            Some(TypeMapping::new("base64uuid::Base64Uuid"))
+        );
+        assert_eq!(config.type_mapping("email"), None);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(config_file("model = [\"models::*\"]").is_err());
+        assert!(config_file("[operations]\nskip = [\"pong\"]").is_err());
+    }
+}
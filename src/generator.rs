@@ -1,131 +1,549 @@
-use crate::args::Args;
+use crate::cli::generate_cli;
 use crate::client_config::generate_client_configs;
-use crate::routes::generate_routes;
-use anyhow::{anyhow, bail, Context, Result};
+use crate::codegen::referenced_crates;
+use crate::config::{Config, Profile, Target};
+use crate::routes::{
+    generate_routes, operation_id, operation_name, operations, operations_mut, METHODS,
+};
+use crate::server::{generate_server, RESERVED_SERVER_NAMES};
+use crate::types::TypeMapper;
+use anyhow::{bail, Context, Result};
 use cargo_toml::{
-    Dependency, DependencyDetail, DepsSet, Inheritable, InheritedDependencyDetail, Manifest,
-    OptionalFile,
+    Dependency, DependencyDetail, DepsSet, FeatureSet, Inheritable, InheritedDependencyDetail,
+    Manifest, OptionalFile,
 };
-use okapi::openapi3::OpenApi;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::Path;
-use std::process::Command;
-
-pub(crate) fn generate_crate(document: OpenApi, path: &Path, args: &Args) -> Result<()> {
-    let status = Command::new("cargo")
-        .arg("new")
-        .arg("--quiet")
-        .args(["--vcs", "none"])
-        .arg("--lib")
-        .args(["--edition", "2021"])
-        .arg(
-            path.to_str()
-                .ok_or_else(|| anyhow!("Failed to convert output OsStr into &str"))?,
-        )
-        .status()
-        .context("Failed to run Cargo")?;
+use okapi::openapi3::{OpenApi, PathItem};
+use okapi::Map;
+use semver::{Op, Version, VersionReq};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, Document, InlineTable, Item, Value};
+
+/// All files making up a generated crate, kept in memory until they get written somewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFiles {
+    /// Contents of the generated `Cargo.toml`
+    pub manifest: String,
+    /// Contents of the generated Rust files, keyed by their path relative to `src`
+    pub sources: BTreeMap<PathBuf, String>,
+}
+
+impl GeneratedFiles {
+    /// Returns the contents of all files of the crate, keyed by their path relative to the crate
+    /// root.
+    pub fn crate_files(&self) -> BTreeMap<PathBuf, String> {
+        let mut files: BTreeMap<PathBuf, String> = self
+            .sources
+            .iter()
+            .map(|(file, contents)| (Path::new("src").join(file), contents.clone()))
+            .collect();
+
+        files.insert(PathBuf::from("Cargo.toml"), self.manifest.clone());
+
+        files
+    }
+
+    /// Returns the contents of the generated modules only, so they can be placed in a directory
+    /// of an existing crate. `lib.rs` becomes the `mod.rs` of that directory.
+    pub fn module_files(&self) -> Result<BTreeMap<PathBuf, String>> {
+        if self.sources.contains_key(Path::new("main.rs")) {
+            bail!("Only library targets can be generated as a module");
+        }
+
+        Ok(self
+            .sources
+            .iter()
+            .map(|(file, contents)| {
+                let file = if file == Path::new("lib.rs") {
+                    PathBuf::from("mod.rs")
+                } else {
+                    file.clone()
+                };
+
+                (file, without_doctests(contents))
+            })
+            .collect())
+    }
+
+    /// Writes the crate to `path`, creating the directories if necessary.
+    pub fn write_crate(&self, path: &Path) -> Result<()> {
+        write_files(path, &self.crate_files())
+    }
+
+    /// Writes only the generated modules into the directory at `path`, leaving all other files
+    /// within it alone.
+    pub fn write_module(&self, path: &Path) -> Result<()> {
+        write_files(path, &self.module_files()?)
+    }
+
+    /// Adds the dependencies and features needed by the generated code to an existing
+    /// `Cargo.toml`.
+    ///
+    /// Missing dependencies are added. Existing ones keep their version if it satisfies the one
+    /// needed by the generated code, get the features it needs added, and keep their source and
+    /// `default-features`. Everything else, including formatting and comments, is kept as is.
+    /// Features are only added when they don't exist yet, except for the default ones, which are
+    /// added to the existing `default` feature unless it already enables one of the generated
+    /// features.
+    pub fn merge_dependencies(&self, manifest: &str) -> Result<String> {
+        let generated: Document = self
+            .manifest
+            .parse()
+            .context("Failed to parse generated `Cargo.toml`")?;
+        let mut existing: Document = manifest
+            .parse()
+            .context("Failed to parse existing `Cargo.toml`")?;
+
+        let dependencies = existing
+            .entry("dependencies")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .context("`dependencies` in `Cargo.toml` is not a table")?;
+
+        if let Some(generated) = generated.get("dependencies").and_then(Item::as_table_like) {
+            for (name, dependency) in generated.iter() {
+                // prefer `name = { .. }` over a separate `[dependencies.name]` table
+                let dependency = dependency
+                    .clone()
+                    .into_value()
+                    .map(Item::Value)
+                    .unwrap_or_else(|item| item);
+
+                match dependencies.get_mut(name) {
+                    Some(existing) => merge_dependency(existing, &dependency)
+                        .with_context(|| format!("Failed to merge dependency `{name}`"))?,
+                    None => {
+                        dependencies.insert(name, dependency);
+                    }
+                }
+            }
+        }
 
-    if !status.success() {
-        if let Some(code) = status.code() {
-            bail!("Cargo exited with non-zero status code: {}", code);
+        if let Some(generated) = generated.get("features").and_then(Item::as_table_like) {
+            let features = existing
+                .entry("features")
+                .or_insert_with(toml_edit::table)
+                .as_table_like_mut()
+                .context("`features` in `Cargo.toml` is not a table")?;
+
+            for (name, feature) in generated.iter() {
+                match features.get_mut(name) {
+                    Some(existing) if name == "default" => {
+                        let existing = existing
+                            .as_array_mut()
+                            .context("`default` feature in `Cargo.toml` is not an array")?;
+
+                        // keep the choice if one of the generated features is already enabled
+                        let enabled = existing.iter().any(|value| {
+                            value
+                                .as_str()
+                                .map_or(false, |value| generated.contains_key(value))
+                        });
+
+                        if !enabled {
+                            let values = feature.as_array().into_iter().flatten();
+                            existing.extend(values.filter_map(|value| value.as_str()));
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        let feature = feature
+                            .clone()
+                            .into_value()
+                            .map(Item::Value)
+                            .unwrap_or_else(|item| item);
+
+                        features.insert(name, feature);
+                    }
+                }
+            }
+        }
+
+        Ok(existing.to_string())
+    }
+
+    /// Inlines `lib.rs` and all modules it declares into a single `pub mod {name} { .. }` item,
+    /// which can be `include!`d by other crates.
+    pub fn to_module(&self, name: &str) -> Result<String> {
+        let lib = self
+            .sources
+            .get(Path::new("lib.rs"))
+            .context("No `lib.rs` has been generated")?;
+
+        let mut output = String::new();
+
+        writeln!(output, "pub mod {name} {{")?;
+
+        for line in lib.lines() {
+            let module = line
+                .strip_prefix("pub mod ")
+                .and_then(|module| module.strip_suffix(';'));
+
+            match module.and_then(|module| {
+                self.sources
+                    .get(Path::new(&format!("{module}.rs")))
+                    .map(|contents| (module, contents))
+            }) {
+                Some((module, contents)) => {
+                    writeln!(output, "pub mod {module} {{")?;
+                    output.push_str(contents);
+                    writeln!(output, "}}")?;
+                }
+                None => writeln!(output, "{line}")?,
+            }
+        }
+
+        writeln!(output, "}}")?;
+
+        Ok(without_doctests(&output))
+    }
+}
+
+/// Merges a generated dependency into the existing entry for the same crate.
+fn merge_dependency(existing: &mut Item, generated: &Item) -> Result<()> {
+    let version = match generated {
+        Item::Value(Value::String(version)) => Some(version.value().as_str()),
+        generated => generated.get("version").and_then(Item::as_str),
+    };
+    let features: Vec<&str> = generated
+        .get("features")
+        .and_then(Item::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    // `name = "1"`, which only needs to become a table if features have to be added
+    if let Some(Value::String(existing_version)) = existing.as_value_mut() {
+        let version = match version {
+            Some(version) if !satisfies(existing_version.value(), version) => version,
+            _ => existing_version.value(),
+        };
+
+        let mut value = if features.is_empty() {
+            Value::from(version)
         } else {
-            bail!("Cargo subprocess was killed by another process or system");
+            let mut table = InlineTable::new();
+            table.insert("version", version.into());
+            table.insert("features", Array::from_iter(features).into());
+            Value::InlineTable(table)
+        };
+
+        // keep comments surrounding the existing entry
+        *value.decor_mut() = existing_version.decor().clone();
+        *existing = Item::Value(value);
+
+        return Ok(());
+    }
+
+    let existing = existing
+        .as_table_like_mut()
+        .context("Dependency is neither a version nor a table")?;
+
+    match (existing.get("version").and_then(Item::as_str), version) {
+        (Some(existing_version), Some(version)) if !satisfies(existing_version, version) => {
+            existing.insert("version", toml_edit::value(version));
         }
+        // dependencies without a version are taken from a path, git or the workspace
+        _ => {}
     }
 
-    edit_cargo_toml(path, args)?;
+    if !features.is_empty() {
+        let existing_features = existing
+            .entry("features")
+            .or_insert_with(|| Item::Value(Array::new().into()))
+            .as_array_mut()
+            .context("`features` of dependency is not an array")?;
+
+        for feature in features {
+            if !existing_features
+                .iter()
+                .any(|existing| existing.as_str() == Some(feature))
+            {
+                existing_features.push(feature);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the `existing` version requirement satisfies the `required` one, which is the case if
+/// the lowest version it allows is allowed by the `required` one as well.
+fn satisfies(existing: &str, required: &str) -> bool {
+    let (existing, required) = match (VersionReq::parse(existing), VersionReq::parse(required)) {
+        (Ok(existing), Ok(required)) => (existing, required),
+        _ => return false,
+    };
+
+    existing
+        .comparators
+        .iter()
+        .filter(|comparator| {
+            matches!(
+                comparator.op,
+                Op::Exact | Op::GreaterEq | Op::Tilde | Op::Caret | Op::Wildcard
+            )
+        })
+        .map(|comparator| {
+            Version::new(
+                comparator.major,
+                comparator.minor.unwrap_or(0),
+                comparator.patch.unwrap_or(0),
+            )
+        })
+        .any(|lowest| required.matches(&lowest))
+}
 
-    let src_directory = path.join("src");
+/// Turns off the doctests of the generated functions, which refer to the generated crate by its
+/// name and so don't compile when the code is part of another crate.
+fn without_doctests(contents: &str) -> String {
+    contents.replace("/// ```no_run", "/// ```ignore")
+}
+
+fn write_files(path: &Path, files: &BTreeMap<PathBuf, String>) -> Result<()> {
+    for (file, contents) in files {
+        let file = path.join(file);
 
-    generate_client_configs(&document.servers, &src_directory)?;
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).context("Failed to create output directory")?;
+        }
 
-    if let Some(components) = document.components {
-        //generate_models(&components, &src_directory)?;
-        generate_routes(&document.paths, &src_directory, &components, &args.models)?;
+        fs::write(&file, contents)
+            .with_context(|| format!("Failed to write `{}` to disk", file.display()))?;
     }
 
     Ok(())
 }
 
-fn open_manifest(path: &Path) -> Result<Manifest> {
-    Manifest::from_path(path).context("Failed to parse `Cargo.toml`")
+/// Generates all files for a crate with the code for the given OpenAPI document.
+pub fn generate(document: &OpenApi, config: &Config) -> Result<GeneratedFiles> {
+    let components = document.components.clone().unwrap_or_default();
+    let paths = select_operations(&document.paths, config)?;
+
+    let mapper = TypeMapper::new(config);
+    let mut sources = BTreeMap::new();
+
+    match config.target {
+        Target::Client => {
+            let (clients, builder) = generate_client_configs(&document.servers, config)?;
+
+            sources.insert(PathBuf::from("clients.rs"), clients);
+            sources.insert(PathBuf::from("builder.rs"), builder);
+            sources.insert(
+                PathBuf::from("lib.rs"),
+                generate_routes(&paths, &components, &mapper)?,
+            );
+        }
+        Target::Server => {
+            sources.insert(
+                PathBuf::from("lib.rs"),
+                generate_server(&paths, &components, &mapper)?,
+            );
+        }
+        Target::Cli => {
+            let (clients, builder) = generate_client_configs(&document.servers, config)?;
+
+            sources.insert(PathBuf::from("clients.rs"), clients);
+            sources.insert(PathBuf::from("builder.rs"), builder);
+            sources.insert(
+                PathBuf::from("lib.rs"),
+                generate_routes(&paths, &components, &mapper)?,
+            );
+            sources.insert(
+                PathBuf::from("main.rs"),
+                generate_cli(&paths, &document.servers, &components, &mapper)?,
+            );
+        }
+    }
+
+    if let Some(types) = mapper.generate_types_module()? {
+        sources.insert(PathBuf::from("types.rs"), types);
+    }
+
+    let manifest = generate_cargo_toml(config, &sources)?;
+
+    Ok(GeneratedFiles { manifest, sources })
 }
 
-fn edit_cargo_toml(path: &Path, args: &Args) -> Result<()> {
-    let path = path.join("Cargo.toml");
+/// Applies the configured operation filters and naming overrides to a copy of the paths.
+fn select_operations(
+    paths: &Map<String, PathItem>,
+    config: &Config,
+) -> Result<Map<String, PathItem>> {
+    let operation_ids: BTreeSet<String> = paths
+        .iter()
+        .flat_map(|(endpoint, item)| {
+            operations(item)
+                .map(move |(method, operation)| operation_id(endpoint, method, operation))
+        })
+        .collect();
+
+    for id in config
+        .include_operations
+        .iter()
+        .chain(&config.exclude_operations)
+        .chain(config.operation_names.keys())
+    {
+        if !operation_ids.contains(id) {
+            bail!("Operation `{id}` from the config does not exist in the OpenAPI document");
+        }
+    }
+
+    let mut paths = paths.clone();
+
+    for (endpoint, item) in paths.iter_mut() {
+        for (method, slot) in METHODS.iter().zip(operations_mut(item)) {
+            let id = match slot {
+                Some(operation) => operation_id(endpoint, method, operation),
+                None => continue,
+            };
+
+            let included =
+                config.include_operations.is_empty() || config.include_operations.contains(&id);
+
+            if !included || config.exclude_operations.contains(&id) {
+                *slot = None;
+            } else if let (Some(operation), Some(name)) =
+                (slot.as_mut(), config.operation_names.get(&id))
+            {
+                // names from the config take precedence over the document
+                operation.operation_id = Some(name.clone());
+                operation.extensions.remove("x-rust-name");
+            }
+        }
+    }
 
-    // https://stackoverflow.com/a/50691004/11494565
-    let mut file = OpenOptions::new()
-        .read(false)
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(&path)
-        .context("Failed to open or create `Cargo.toml`")?;
+    // every operation becomes a function in the same module, together with the variants of the
+    // client, so their names need to be unique. Which variants are generated depends on the
+    // responses, so all of them are reserved
+    let suffixes: &[&str] = match config.target {
+        Target::Client | Target::Cli => &["", "_with_options", "_with_response", "_stream"],
+        Target::Server => &[""],
+    };
+    let reserved: &[&str] = match config.target {
+        Target::Client | Target::Cli => &[],
+        Target::Server => RESERVED_SERVER_NAMES,
+    };
+    let mut names: BTreeMap<String, String> = BTreeMap::new();
 
-    let mut manifest = open_manifest(&path)?;
+    for (endpoint, item) in &paths {
+        for (method, operation) in operations(item) {
+            let name = operation_name(endpoint, method, operation);
+            let description = format!(
+                "`{}` ({method} {endpoint})",
+                operation_id(endpoint, method, operation)
+            );
+            let paginated = operation.extensions.contains_key("x-pagination");
+
+            for suffix in suffixes.iter().chain(paginated.then_some(&"_all")) {
+                let function = format!("{}{suffix}", name.trim_start_matches("r#"));
+
+                if reserved.contains(&function.as_str()) {
+                    bail!(
+                        "Operation {description} generates a function named \
+                        `{function}`, which is reserved for the generated code, rename it with \
+                        `x-rust-name` or the `names` config"
+                    );
+                }
+
+                if let Some(existing) = names.insert(function.clone(), description.clone()) {
+                    bail!(
+                        "Operations {existing} and {description} both generate a function named \
+                        `{function}`, rename one of them with `x-rust-name` or the `names` config"
+                    );
+                }
+            }
+        }
+    }
 
-    let mut package_metadata = manifest
+    Ok(paths)
+}
+
+fn generate_cargo_toml(config: &Config, sources: &BTreeMap<PathBuf, String>) -> Result<String> {
+    // the same manifest `cargo new` would create
+    let mut manifest = Manifest::from_str(&format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+        config.name
+    ))
+    .context("Failed to create `Cargo.toml`")?;
+
+    let package_metadata = manifest
         .package
         .as_mut()
         .context("`Cargo.toml` does not contain a [package] section")?;
 
-    if args.workspace {
+    if config.workspace {
         package_metadata.version = Inheritable::Inherited { workspace: true };
-    } else if let Some(version) = args.crate_version.as_ref() {
+    } else if let Some(version) = config.crate_version.as_ref() {
         package_metadata.version = Inheritable::Set(version.clone());
     }
 
-    if let Some(license) = args.license.as_ref() {
-        package_metadata.license = if args.workspace {
+    if let Some(license) = config.license.as_ref() {
+        package_metadata.license = if config.workspace {
             Some(Inheritable::Inherited { workspace: true })
         } else {
             Some(Inheritable::Set(license.clone()))
         }
     }
 
-    if let Some(description) = args.description.as_ref() {
+    if let Some(description) = config.description.as_ref() {
         package_metadata.description = Some(Inheritable::Set(description.clone()));
     }
 
-    if let Some(readme) = args.readme.as_ref() {
+    if let Some(readme) = config.readme.as_ref() {
         package_metadata.readme =
             Inheritable::Set(OptionalFile::Path(Path::new(readme).to_path_buf()));
     }
 
-    if let Some(documentation) = args.documentation.as_ref() {
-        package_metadata.documentation = if args.workspace {
+    if let Some(documentation) = config.documentation.as_ref() {
+        package_metadata.documentation = if config.workspace {
             Some(Inheritable::Inherited { workspace: true })
         } else {
             Some(Inheritable::Set(documentation.clone()))
         }
     }
 
-    if let Some(repository) = args.repository.as_ref() {
-        package_metadata.repository = if args.workspace {
+    if let Some(repository) = config.repository.as_ref() {
+        package_metadata.repository = if config.workspace {
             Some(Inheritable::Inherited { workspace: true })
         } else {
             Some(Inheritable::Set(repository.clone()))
         }
     }
 
-    add_dependencies(&mut manifest.dependencies, args)?;
+    add_dependencies(&mut manifest.dependencies, config)?;
+    add_type_dependencies(&mut manifest.dependencies, sources)?;
+    add_features(&mut manifest.features, config);
 
     // workaround for "values must be emitted before tables" error which happens for some people
     // https://gitlab.com/crates.rs/cargo_toml/-/issues/3#note_687730489
     let value =
         toml::Value::try_from(&manifest).context("Failed to convert `Cargo.toml` to toml value")?;
-    let serialized =
-        toml::to_string(&value).context("Failed to serialize `Cargo.toml` value to string")?;
 
-    file.write_all(serialized.as_bytes())
-        .context("Failed to write `Cargo.toml` to disk")?;
+    toml::to_string(&value).context("Failed to serialize `Cargo.toml` value to string")
+}
 
-    Ok(())
+/// Lets the generated crate choose the TLS implementation used by `reqwest`.
+fn add_features(features: &mut FeatureSet, config: &Config) {
+    if config.target == Target::Server {
+        return;
+    }
+
+    features.insert("default".to_owned(), vec!["rustls-tls".to_owned()]);
+    features.insert(
+        "rustls-tls".to_owned(),
+        vec!["reqwest/rustls-tls".to_owned()],
+    );
+    features.insert(
+        "native-tls".to_owned(),
+        vec!["reqwest/native-tls".to_owned()],
+    );
 }
 
-fn add_dependencies(dependencies: &mut DepsSet, args: &Args) -> Result<()> {
+fn add_dependencies(dependencies: &mut DepsSet, config: &Config) -> Result<()> {
     // serde
     dependencies.insert(
         "serde".to_owned(),
@@ -148,52 +566,138 @@ fn add_dependencies(dependencies: &mut DepsSet, args: &Args) -> Result<()> {
     // secrecy
     dependencies.insert("secrecy".to_string(), Dependency::Simple("0".to_string()));
 
-    // reqwest
-    dependencies.insert(
-        "reqwest".to_owned(),
-        Dependency::Detailed(DependencyDetail {
-            default_features: false,
-            features: vec![
-                "gzip".to_owned(),
-                "json".to_owned(),
-                "multipart".to_owned(),
-                "rustls-tls".to_owned(),
-            ],
-            version: Some("0.11".to_owned()),
-            ..Default::default()
-        }),
-    );
+    match config.target {
+        Target::Client | Target::Cli => {
+            // reqwest
+            dependencies.insert(
+                "reqwest".to_owned(),
+                Dependency::Detailed(DependencyDetail {
+                    default_features: false,
+                    features: vec![
+                        "gzip".to_owned(),
+                        "json".to_owned(),
+                        "multipart".to_owned(),
+                        // streaming request and response bodies
+                        "stream".to_owned(),
+                    ],
+                    version: Some("0.11".to_owned()),
+                    ..Default::default()
+                }),
+            );
 
-    // base64uuid
-    dependencies.insert(
-        "base64uuid".to_string(),
-        fp_dependency("base64uuid", args, Vec::new()),
-    );
+            if config.target == Target::Cli {
+                // clap
+                dependencies.insert(
+                    "clap".to_owned(),
+                    Dependency::Detailed(DependencyDetail {
+                        features: vec!["derive".to_owned(), "env".to_owned()],
+                        default_features: true,
+                        version: Some("4".to_owned()),
+                        ..Default::default()
+                    }),
+                );
 
-    // fiberplane-models
-    dependencies.insert(
-        "fiberplane-models".to_string(),
-        fp_dependency("fiberplane-models", args, Vec::new()),
-    );
+                // tokio
+                dependencies.insert(
+                    "tokio".to_owned(),
+                    Dependency::Detailed(DependencyDetail {
+                        features: vec!["macros".to_owned(), "rt-multi-thread".to_owned()],
+                        default_features: true,
+                        version: Some("1".to_owned()),
+                        ..Default::default()
+                    }),
+                );
+
+                // serde_yaml
+                dependencies.insert(
+                    "serde_yaml".to_string(),
+                    Dependency::Simple("0.9".to_string()),
+                );
+            }
+        }
+        Target::Server => {
+            // axum
+            dependencies.insert("axum".to_string(), Dependency::Simple("0.6".to_string()));
+
+            // async-trait
+            dependencies.insert(
+                "async-trait".to_string(),
+                Dependency::Simple("0.1".to_string()),
+            );
+        }
+    }
+
+    if config.profile == Profile::Fiberplane {
+        // base64uuid
+        dependencies.insert(
+            "base64uuid".to_string(),
+            fp_dependency("base64uuid", config, Vec::new()),
+        );
+
+        // fiberplane-models
+        dependencies.insert(
+            "fiberplane-models".to_string(),
+            fp_dependency("fiberplane-models", config, Vec::new()),
+        );
+    }
 
     dependencies.insert("bytes".to_string(), Dependency::Simple("1".to_string()));
 
     Ok(())
 }
 
+/// Adds the crates of the types that are only referenced for some schema formats, if the
+/// generated code uses them.
+fn add_type_dependencies(
+    dependencies: &mut DepsSet,
+    sources: &BTreeMap<PathBuf, String>,
+) -> Result<()> {
+    let mut used = BTreeSet::new();
+
+    for source in sources.values() {
+        used.extend(referenced_crates(source)?);
+    }
+
+    for (name, version, features) in [
+        ("uuid", "1", &["serde"][..]),
+        ("url", "2", &["serde"]),
+        ("base64", "0.21", &[]),
+        ("futures", "0.3", &[]),
+    ] {
+        if !used.contains(name) {
+            continue;
+        }
+
+        let dependency = if features.is_empty() {
+            Dependency::Simple(version.to_owned())
+        } else {
+            Dependency::Detailed(DependencyDetail {
+                features: features.iter().map(|feature| feature.to_string()).collect(),
+                default_features: true,
+                version: Some(version.to_owned()),
+                ..Default::default()
+            })
+        };
+
+        dependencies.insert(name.to_owned(), dependency);
+    }
+
+    Ok(())
+}
+
 /// declare a dependency which lives within the fiberplane repository
-fn fp_dependency(name: &str, args: &Args, features: Vec<String>) -> Dependency {
-    if args.workspace {
+fn fp_dependency(name: &str, config: &Config, features: Vec<String>) -> Dependency {
+    if config.workspace {
         Dependency::Inherited(InheritedDependencyDetail {
             features,
             workspace: true,
             ..Default::default()
         })
-    } else if args.local {
+    } else if config.local {
         Dependency::Detailed(DependencyDetail {
             features,
             path: Some(format!("../{name}")),
-            version: args.crate_version.as_ref().cloned(),
+            version: config.crate_version.as_ref().cloned(),
             ..Default::default()
         })
     } else {
@@ -205,3 +709,324 @@ fn fp_dependency(name: &str, args: &Args, features: Vec<String>) -> Dependency {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated(manifest: &str) -> GeneratedFiles {
+        GeneratedFiles {
+            manifest: manifest.to_owned(),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn merge_dependencies_keeps_existing_entries() {
+        let files = generated(
+            r#"
+[dependencies]
+anyhow = "1"
+reqwest = { version = "0.11", default-features = false, features = ["json", "stream"] }
+serde = { version = "1", default-features = false, features = ["derive"] }
+url = { version = "2", features = ["serde"] }
+"#,
+        );
+        let existing = r#"[package]
+name = "api"
+
+# shared with the handwritten code
+[dependencies]
+anyhow = "1.0.66" # errors
+reqwest = { version = "0.10", features = ["blocking"] }
+serde = "1.0.100"
+
+[dependencies.url]
+workspace = true
+"#;
+
+        assert_eq!(
+            files.merge_dependencies(existing).unwrap(),
+            r#"[package]
+name = "api"
+
+# shared with the handwritten code
+[dependencies]
+anyhow = "1.0.66" # errors
+reqwest = { version = "0.11", features = ["blocking", "json", "stream"] }
+serde = { version = "1.0.100", features = ["derive"] }
+
+[dependencies.url]
+workspace = true
+features = ["serde"]
+"#
+        );
+    }
+
+    #[test]
+    fn merge_dependencies_adds_missing_entries_and_features() {
+        let files = generated(
+            r#"
+[dependencies]
+bytes = "1"
+
+[features]
+default = ["rustls-tls"]
+native-tls = ["reqwest/native-tls"]
+rustls-tls = ["reqwest/rustls-tls"]
+"#,
+        );
+        let existing = r#"[package]
+name = "api"
+
+[features]
+default = ["native-tls"]
+"#;
+
+        assert_eq!(
+            files.merge_dependencies(existing).unwrap(),
+            r#"[package]
+name = "api"
+
+[features]
+default = ["native-tls"]
+native-tls = ["reqwest/native-tls"]
+rustls-tls = ["reqwest/rustls-tls"]
+
+[dependencies]
+bytes = "1"
+"#
+        );
+    }
+
+    #[test]
+    fn to_module_inlines_the_declared_modules() {
+        let files = GeneratedFiles {
+            manifest: String::new(),
+            sources: [
+                (
+                    PathBuf::from("lib.rs"),
+                    "pub mod clients;\npub mod models {\n}\n/// ```no_run\npub fn get() {}\n"
+                        .to_owned(),
+                ),
+                (
+                    PathBuf::from("clients.rs"),
+                    "pub struct ApiClient;\n".to_owned(),
+                ),
+            ]
+            .into(),
+        };
+
+        assert_eq!(
+            files.to_module("api").unwrap(),
+            "pub mod api {\npub mod clients {\npub struct ApiClient;\n}\npub mod models {\n}\n\
+            /// ```ignore\npub fn get() {}\n}\n"
+        );
+        assert_eq!(
+            GeneratedFiles {
+                manifest: String::new(),
+                sources: BTreeMap::new(),
+            }
+            .to_module("api")
+            .unwrap_err()
+            .to_string(),
+            "No `lib.rs` has been generated"
+        );
+    }
+
+    #[test]
+    fn satisfies_compares_lowest_versions() {
+        assert!(satisfies("1.0.100", "1"));
+        assert!(satisfies("=1.2.3", "1"));
+        assert!(satisfies("0.11.4", "0.11"));
+        assert!(!satisfies("0.10", "0.11"));
+        assert!(!satisfies("2", "1"));
+    }
+
+    fn paths(yaml: &str) -> Map<String, PathItem> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn select_error(yaml: &str, target: Target) -> String {
+        let config = Config {
+            target,
+            ..Config::new("api")
+        };
+
+        select_operations(&paths(yaml), &config)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn operation_ids_are_derived_from_method_and_path() {
+        let paths = paths(
+            r#"
+/workspaces/{workspace_id}/notebooks:
+  get:
+    responses: {}
+  post:
+    operationId: notebook_create
+    x-rust-name: createNotebook
+    responses: {}
+"#,
+        );
+        let item = &paths["/workspaces/{workspace_id}/notebooks"];
+        let get = item.get.as_ref().unwrap();
+        let post = item.post.as_ref().unwrap();
+
+        assert_eq!(
+            operation_id("/workspaces/{workspace_id}/notebooks", "GET", get),
+            "get_workspaces_by_workspace_id_notebooks"
+        );
+        assert_eq!(
+            operation_id("/workspaces/{workspace_id}/notebooks", "POST", post),
+            "notebook_create"
+        );
+        assert_eq!(
+            operation_name("/workspaces/{workspace_id}/notebooks", "POST", post),
+            "create_notebook"
+        );
+    }
+
+    #[test]
+    fn colliding_operation_names_are_rejected() {
+        let error = select_error(
+            r#"
+/notebooks:
+  get:
+    operationId: listNotebooks
+    responses: {}
+/notebooks/all:
+  get:
+    operationId: list_notebooks
+    responses: {}
+"#,
+            Target::Client,
+        );
+
+        assert_eq!(
+            error,
+            "Operations `listNotebooks` (GET /notebooks) and `list_notebooks` (GET /notebooks/all) \
+            both generate a function named `list_notebooks`, rename one of them with \
+            `x-rust-name` or the `names` config"
+        );
+    }
+
+    #[test]
+    fn colliding_variant_names_are_rejected() {
+        let yaml = r#"
+/files:
+  get:
+    operationId: download
+    x-pagination:
+      cursor: cursor
+    responses: {}
+/files/stream:
+  get:
+    operationId: download_stream
+    responses: {}
+"#;
+
+        assert!(select_error(yaml, Target::Client).contains(
+            "Operations `download` (GET /files) and `download_stream` (GET /files/stream) both \
+            generate a function named `download_stream`"
+        ));
+        assert!(select_error(yaml, Target::Cli).contains("`download_stream`"));
+
+        // the server doesn't generate variants
+        let config = Config {
+            target: Target::Server,
+            ..Config::new("api")
+        };
+        assert!(select_operations(&paths(yaml), &config).is_ok());
+
+        let error = select_error(
+            r#"
+/files:
+  get:
+    operationId: download
+    x-pagination:
+      cursor: cursor
+    responses: {}
+/files/all:
+  get:
+    operationId: download_all
+    responses: {}
+"#,
+            Target::Client,
+        );
+        assert!(error.contains("`download_all`"), "{error}");
+    }
+
+    #[test]
+    fn reserved_server_names_are_rejected() {
+        let yaml = r#"
+/router:
+  get:
+    operationId: router
+    responses: {}
+"#;
+
+        assert_eq!(
+            select_error(yaml, Target::Server),
+            "Operation `router` (GET /router) generates a function named `router`, which is \
+            reserved for the generated code, rename it with `x-rust-name` or the `names` config"
+        );
+
+        let config = Config {
+            operation_names: [("router".to_owned(), "get_router".to_owned())].into(),
+            ..Config::new("api")
+        };
+        assert!(select_operations(&paths(yaml), &config).is_ok());
+    }
+
+    #[test]
+    fn server_operations_can_be_named_like_methods() {
+        let document: OpenApi = serde_yaml::from_str(
+            r#"
+openapi: 3.0.0
+info:
+  title: Methods
+  version: "1"
+paths:
+  /items:
+    get:
+      operationId: get
+      responses: {}
+    post:
+      operationId: post
+      responses: {}
+  /items/{id}:
+    parameters:
+      - { name: id, in: path, required: true, schema: { type: string } }
+    delete:
+      operationId: delete
+      responses: {}
+    head:
+      operationId: head
+      responses: {}
+    options:
+      operationId: options
+      responses: {}
+    trace:
+      operationId: trace
+      responses: {}
+"#,
+        )
+        .unwrap();
+        let config = Config {
+            target: Target::Server,
+            ..Config::new("api")
+        };
+
+        let files = generate(&document, &config).unwrap();
+        let lib = files.sources[Path::new("lib.rs")].replace(char::is_whitespace, "");
+
+        assert!(lib.contains(".route(\"/items\",axum::routing::get(get::<S>).post(post::<S>))"));
+        assert!(lib.contains(
+            ".route(\"/items/:id\",axum::routing::delete(delete::<S>).options(options::<S>)\
+            .head(head::<S>).trace(trace::<S>),)"
+        ));
+    }
+}
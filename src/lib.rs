@@ -0,0 +1,91 @@
+//! Generates Rust code from OpenAPI 3.0 specifications.
+//!
+//! Besides the `fp-openapi-rust-gen` binary, the generator can be used as a library, for example
+//! from a `build.rs` script so the generated code doesn't need to be committed:
+//!
+//! ```no_run
+//! // build.rs
+//! use fp_openapi_rust_gen::{build, Config};
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     let config = Config {
+//!         models: vec!["fiberplane_models::notebooks::*".to_owned()],
+//!         ..Config::new("api")
+//!     };
+//!
+//!     build("openapi.yaml", &config)
+//! }
+//! ```
+//!
+//! The generated module can then be included in the crate with:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/api.rs"));
+//! ```
+#![forbid(unsafe_code)]
+
+use anyhow::{bail, Context, Result};
+use codegen::snake_case;
+use okapi::openapi3::OpenApi;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+mod cli;
+mod client_config;
+mod codegen;
+mod config;
+mod generator;
+mod pagination;
+mod routes;
+mod server;
+mod types;
+
+pub use config::{
+    Config, ConfigFile, OperationFilter, Profile, QuerySerialization, Target, TypeMapping,
+    CONFIG_FILE_NAME,
+};
+pub use generator::{generate, GeneratedFiles};
+
+/// Reads and parses the OpenAPI document at `path`.
+pub fn load_document(path: &Path) -> Result<OpenApi> {
+    if !path.is_file() {
+        bail!("Open api file not found");
+    }
+
+    let extension = path.extension().unwrap_or_default();
+
+    if extension != "yml" && extension != "yaml" {
+        bail!("Input needs to be a YAML file (extension: .yml or .yaml)");
+    }
+
+    let file = File::open(path).context("Failed to read OpenAPI document")?;
+    let reader = BufReader::new(file);
+
+    serde_yaml::from_reader(reader).context("Failed to parse OpenAPI document")
+}
+
+/// Generates the code for the OpenAPI document at `path` from within a build script.
+///
+/// The generated code is wrapped in a single module named after the snake cased `config.name`
+/// and written to `$OUT_DIR/{module}.rs`. Cargo is instructed to rerun the build script whenever
+/// the document changes.
+pub fn build(path: impl AsRef<Path>, config: &Config) -> Result<()> {
+    let path = path.as_ref();
+
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let document = load_document(path)?;
+    let files = generate(&document, config)?;
+
+    let out_dir = env::var_os("OUT_DIR")
+        .context("`OUT_DIR` is not set, `build` needs to be called from a build script")?;
+    let module = snake_case(&config.name).replace("r#", "");
+
+    fs::write(
+        Path::new(&out_dir).join(format!("{module}.rs")),
+        files.to_module(&module)?,
+    )
+    .context("Failed to write generated module")
+}
@@ -3,43 +3,65 @@
 use anyhow::{bail, Context, Result};
 use args::Args;
 use clap::Parser;
-use okapi::openapi3::OpenApi;
+use fp_openapi_rust_gen::{generate, load_document};
+use similar::TextDiff;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 mod args;
-mod client_config;
-mod generator;
-mod routes;
-mod types;
 
 fn main() -> Result<()> {
-    let args: Args = Args::parse();
+    let options = Args::parse().into_options()?;
 
-    let path = args.file.as_path();
+    let document = load_document(options.file.as_path())?;
+    let files = generate(&document, &options.config)?;
 
-    if !path.is_file() {
-        bail!("Open api file not found");
-    }
+    let output = options.output.as_path();
+
+    if options.check {
+        let mut expected = if options.module_only {
+            files.module_files()?
+        } else {
+            files.crate_files()
+        };
 
-    let extension = path.extension().unwrap_or_default();
+        if let Some(manifest_path) = &options.manifest_path {
+            let manifest =
+                fs::read_to_string(manifest_path).context("Failed to read `Cargo.toml`")?;
 
-    if extension != "yml" && extension != "yaml" {
-        bail!("Input needs to be a YAML file (extension: .yml or .yaml)");
+            // paths are compared relative to the output directory, so use the absolute path here
+            expected.insert(
+                env::current_dir()?.join(manifest_path),
+                files.merge_dependencies(&manifest)?,
+            );
+        }
+
+        if !check_files(output, &expected, !options.module_only)? {
+            bail!("Generated code is out of date. Run the generator again to update it");
+        }
+
+        return Ok(());
     }
 
-    let file = File::open(path).context("Failed to read OpenAPI document")?;
-    let reader = BufReader::new(file);
+    if options.module_only {
+        files.write_module(output)?;
 
-    let document: OpenApi =
-        serde_yaml::from_reader(reader).context("Failed to parse OpenAPI document")?;
+        if let Some(manifest_path) = &options.manifest_path {
+            let manifest =
+                fs::read_to_string(manifest_path).context("Failed to read `Cargo.toml`")?;
 
-    let output = args.output.as_path();
+            fs::write(manifest_path, files.merge_dependencies(&manifest)?)
+                .context("Failed to write `Cargo.toml` to disk")?;
+        }
+
+        return Ok(());
+    }
 
     if output.exists() {
-        if args.force {
+        if options.force {
             // Windows does not delete the directory until the last handle to it is closed: https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-removedirectorya#remarks
             // A handle gets created by `clap` to it however (as its passed as a PathBuf in CLI args)
             // To work around this, rename the old directory and delete that. I hate Windows.
@@ -52,5 +74,126 @@ fn main() -> Result<()> {
         }
     }
 
-    generator::generate_crate(document, output, &args)
+    files.write_crate(output)
+}
+
+/// Compares the expected files against the ones in `path`, printing a unified diff for every
+/// file that differs. When `exhaustive` is set, files in `path` that aren't expected (except for
+/// those created by Cargo) are reported too. Returns whether all files are up to date.
+fn check_files(
+    path: &Path,
+    expected: &BTreeMap<PathBuf, String>,
+    exhaustive: bool,
+) -> Result<bool> {
+    let mut up_to_date = true;
+
+    for (file, contents) in expected {
+        let file = path.join(file);
+        let name = file.display().to_string();
+
+        let existing = match fs::read_to_string(&file) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                up_to_date = false;
+                println!("Missing file: {name}");
+                continue;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to read `{name}`")),
+        };
+
+        if existing != *contents {
+            up_to_date = false;
+
+            print!(
+                "{}",
+                TextDiff::from_lines(&existing, contents)
+                    .unified_diff()
+                    .header(&name, &name)
+            );
+        }
+    }
+
+    if exhaustive {
+        let expected: BTreeSet<PathBuf> = expected.keys().map(|file| path.join(file)).collect();
+        let mut existing = Vec::new();
+        list_files(path, &mut existing)?;
+
+        for file in existing {
+            if !expected.contains(&file) {
+                up_to_date = false;
+                println!("Unexpected file: {}", file.display());
+            }
+        }
+    }
+
+    Ok(up_to_date)
+}
+
+/// Adds the files in `dir` and its subdirectories to `files`, skipping the `target` directory
+/// and `Cargo.lock` that Cargo creates when the crate is built on its own.
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read `{}`", dir.display())),
+    };
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read `{}`", dir.display()))?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() != "target" {
+                list_files(&path, files)?;
+            }
+        } else if entry.file_name() != "Cargo.lock" {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_dir(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("fp-openapi-gen-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("src")).unwrap();
+        fs::create_dir_all(path.join("target/debug")).unwrap();
+        fs::write(path.join("src/lib.rs"), "// lib\n").unwrap();
+        fs::write(path.join("target/debug/output"), "").unwrap();
+        fs::write(path.join("Cargo.lock"), "").unwrap();
+        path
+    }
+
+    fn expected(files: &[(&str, &str)]) -> BTreeMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(file, contents)| (PathBuf::from(file), contents.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn build_output_is_ignored() {
+        let path = output_dir("ignored");
+        assert!(check_files(&path, &expected(&[("src/lib.rs", "// lib\n")]), true).unwrap());
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn missing_and_unexpected_files_are_reported() {
+        let path = output_dir("reported");
+        let files = expected(&[("src/lib.rs", "// lib\n"), ("src/types.rs", "")]);
+        assert!(!check_files(&path, &files, true).unwrap());
+
+        let files = expected(&[("src/types.rs", "")]);
+        fs::write(path.join("src/types.rs"), "").unwrap();
+        assert!(!check_files(&path, &files, true).unwrap());
+        // other files are left alone when only modules are generated
+        assert!(check_files(&path, &files, false).unwrap());
+        fs::remove_dir_all(path).unwrap();
+    }
 }
@@ -0,0 +1,488 @@
+use crate::codegen::{doc, ident, parse_type, pascal_case, snake_case};
+use crate::routes::{generate_request, ResponseType, Route, RouteParameter, RouteResponse};
+use crate::types::{is_nullable, TypeMapper};
+use anyhow::{anyhow, bail, Context, Result};
+use okapi::openapi3::Components;
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use schemars::schema::{Schema, SchemaObject};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The `x-pagination` extension of an operation, describing how to get from one page to the next.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Pagination {
+    /// Name of the query parameter taking the cursor of the page
+    cursor: Option<String>,
+    /// Name of the response property containing the cursor of the next page
+    next_cursor: Option<String>,
+    /// Name of the query parameter taking the page number
+    page: Option<String>,
+    /// Number of the first page, defaults to 1
+    first_page: Option<u64>,
+    /// Whether the URL of the next page is taken from the `Link` header with `rel="next"`
+    #[serde(default)]
+    link_header: bool,
+    /// Name of the response property containing the items, if the response isn't an array
+    items: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PaginationStyle {
+    /// The cursor of the next page is part of the response
+    Cursor,
+    /// Pages are numbered
+    Page,
+    /// The URL of the next page is in the `Link` header
+    Link,
+}
+
+pub(crate) struct RoutePagination {
+    pub(crate) style: PaginationStyle,
+    /// The cursor or page number parameter, which is filled in for every page
+    parameter: Option<usize>,
+    first_page: u64,
+    item_type: String,
+    /// Expression taking the items out of the `response`
+    items: TokenStream,
+    /// Expression taking the cursor of the next page out of the `response`, as an `Option`
+    next_cursor: Option<TokenStream>,
+    /// Whether the `response` is converted to a JSON value before taking the properties out of it
+    by_value: bool,
+}
+
+impl RoutePagination {
+    pub(crate) fn analyze(
+        extension: &Value,
+        parameters: &[RouteParameter],
+        response: &RouteResponse,
+        schema: Option<&SchemaObject>,
+        prefix: &str,
+        components: &Components,
+        mapper: &TypeMapper,
+    ) -> Result<Self> {
+        let pagination: Pagination = serde_json::from_value(extension.clone())
+            .context("Failed to parse `x-pagination` extension")?;
+
+        if response.kind != ResponseType::Json {
+            bail!("paginated operations need a JSON response");
+        }
+
+        let style = match (&pagination.cursor, &pagination.page, pagination.link_header) {
+            (Some(_), None, false) => PaginationStyle::Cursor,
+            (None, Some(_), false) => PaginationStyle::Page,
+            (None, None, true) => PaginationStyle::Link,
+            _ => bail!("exactly one of `cursor`, `page` or `link-header` needs to be set"),
+        };
+
+        let parameter = match pagination.cursor.as_ref().or(pagination.page.as_ref()) {
+            Some(name) => {
+                let index = parameters
+                    .iter()
+                    .position(|parameter| parameter.location == "query" && &parameter.name == name)
+                    .ok_or_else(|| anyhow!("there is no query parameter `{name}`"))?;
+
+                if style == PaginationStyle::Cursor && parameters[index].required {
+                    bail!("the cursor parameter `{name}` needs to be optional");
+                }
+
+                Some(index)
+            }
+            None => None,
+        };
+
+        // the page itself may be a reference to one of the component schemas. The struct for it
+        // comes from the models, so the names and types of its fields are unknown, and the
+        // properties are taken from its JSON value instead
+        let by_value = matches!(
+            schema,
+            Some(SchemaObject {
+                reference: Some(_),
+                ..
+            })
+        );
+        let page = match schema {
+            Some(SchemaObject {
+                reference: Some(reference),
+                ..
+            }) => reference
+                .strip_prefix("#/components/schemas/")
+                .and_then(|name| components.schemas.get(name)),
+            schema => schema,
+        };
+
+        let (item_type, items) = match &pagination.items {
+            Some(name) => {
+                let (schema, required) = property(page, name)?;
+                let type_ = mapper.map_type(
+                    schema,
+                    &format!("{prefix}Response{}", pascal_case(name)),
+                    false,
+                )?;
+                let item_type = vec_item(&type_)
+                    .ok_or_else(|| anyhow!("the `{name}` property needs to be an array"))?;
+
+                let items = if by_value {
+                    let items = value_accessor(name);
+                    quote! { #items.unwrap_or_default() }
+                } else if required && !is_nullable(schema) {
+                    let field = ident(&snake_case(name));
+                    quote! { response.#field }
+                } else {
+                    let items = accessor(name, required, is_nullable(schema));
+                    quote! { #items.unwrap_or_default() }
+                };
+
+                (item_type.to_owned(), items)
+            }
+            None => {
+                let item_type = vec_item(&response.type_).ok_or_else(|| {
+                    anyhow!("`items` needs to be set if the response isn't an array")
+                })?;
+
+                (item_type.to_owned(), quote! { response })
+            }
+        };
+
+        let next_cursor = match (style, &pagination.next_cursor) {
+            (PaginationStyle::Cursor, Some(name)) => {
+                let (schema, required) = property(page, name)?;
+
+                Some(if by_value {
+                    value_accessor(name)
+                } else {
+                    accessor(name, required, is_nullable(schema))
+                })
+            }
+            (PaginationStyle::Cursor, None) => bail!("`next-cursor` needs to be set with `cursor`"),
+            (_, Some(_)) => bail!("`next-cursor` can only be set with `cursor`"),
+            (_, None) => None,
+        };
+
+        Ok(Self {
+            style,
+            parameter,
+            first_page: pagination.first_page.unwrap_or(1),
+            item_type,
+            items,
+            next_cursor,
+            by_value,
+        })
+    }
+}
+
+/// Returns the schema of a property of the page and whether it's required.
+fn property<'a>(page: Option<&'a SchemaObject>, name: &str) -> Result<(&'a SchemaObject, bool)> {
+    let object = page
+        .and_then(|page| page.object.as_ref())
+        .ok_or_else(|| anyhow!("the response needs to be an object to have a `{name}` property"))?;
+
+    match object.properties.get(name) {
+        Some(Schema::Object(schema)) => Ok((schema, object.required.contains(name))),
+        _ => bail!("the response has no `{name}` property"),
+    }
+}
+
+/// Generates the expression taking a property out of the `response` as an `Option`, following the
+/// types used for the fields of the generated structs.
+fn accessor(name: &str, required: bool, nullable: bool) -> TokenStream {
+    let field = ident(&snake_case(name));
+
+    match (required, nullable) {
+        (true, false) => quote! { Some(response.#field) },
+        (false, true) => quote! { response.#field.into_option() },
+        _ => quote! { response.#field },
+    }
+}
+
+/// Generates the expression deserializing a property of the `response` converted to a JSON value,
+/// as an `Option`.
+fn value_accessor(name: &str) -> TokenStream {
+    quote! {
+        serde_json::from_value::<Option<_>>(
+            response.get_mut(#name).map(std::mem::take).unwrap_or_default(),
+        )?
+    }
+}
+
+fn vec_item(type_: &str) -> Option<&str> {
+    type_.strip_prefix("Vec<")?.strip_suffix('>')
+}
+
+/// Generates the `_all` variant of paginated routes, which returns a stream of the items of all
+/// pages.
+pub(crate) fn generate_paginated_route(route: &Route) -> Result<Option<TokenStream>> {
+    let pagination = match &route.pagination {
+        Some(pagination) => pagination,
+        None => return Ok(None),
+    };
+
+    let function = route.name.trim_start_matches("r#");
+    let docs = doc(&format!(
+        "Like [`{function}`], but returns a stream of the items of all pages.\n\n\
+        Pages are fetched lazily, whenever the items of the previous page have been consumed.\n\
+        The stream ends after the last page, or the first page without any items."
+    ));
+    // calls the deprecated function of the operation itself
+    let deprecated = route
+        .operation
+        .deprecated
+        .then(|| quote! { #[deprecated] #[allow(deprecated)] });
+    let name = ident(&format!("{function}_all"));
+    let item_type = parse_type(&pagination.item_type)?;
+
+    let mut parameters = Vec::new();
+    // owned values are cloned for every page, references can simply be copied
+    let mut clones = Vec::new();
+    let mut arguments = Vec::new();
+
+    for (index, parameter) in route.parameters.iter().enumerate() {
+        let ident = ident(&parameter.ident);
+
+        if pagination.parameter == Some(index) {
+            arguments.push(if pagination.style == PaginationStyle::Page {
+                if parameter.required {
+                    quote! { #ident }
+                } else {
+                    quote! { Some(#ident) }
+                }
+            } else if !parameter.type_.starts_with('&') {
+                quote! { #ident }
+            } else if parameter.owned_type == "String" {
+                quote! { #ident.as_deref() }
+            } else {
+                quote! { #ident.as_ref() }
+            });
+            continue;
+        }
+
+        let type_ = parse_type(&parameter.type_.replace('&', "&'a "))?;
+
+        parameters.push(if parameter.required {
+            quote! { #ident: #type_ }
+        } else {
+            quote! { #ident: Option<#type_> }
+        });
+
+        if !parameter.type_.starts_with('&') {
+            clones.push(quote! { let #ident = #ident.clone(); });
+        }
+
+        arguments.push(quote! { #ident });
+    }
+
+    let payload = match &route.body {
+        Some(body) => {
+            let type_ = parse_type(&body.type_.replace('&', "&'a "))?;
+
+            if !body.type_.starts_with('&') {
+                clones.push(quote! { let payload = payload.clone(); });
+            }
+
+            arguments.push(quote! { payload });
+            Some(quote! { payload: #type_ })
+        }
+        None => None,
+    };
+
+    let items = &pagination.items;
+    // called by its path, as a parameter can have the same name
+    let base = ident(&route.name);
+    let to_value = pagination
+        .by_value
+        .then(|| quote! { let mut response = serde_json::to_value(response)?; });
+
+    let pages = match pagination.style {
+        PaginationStyle::Cursor => {
+            let parameter = pagination_parameter(route, pagination)?;
+            let cursor = ident(&parameter.ident);
+            let cursor_type = parse_type(&parameter.owned_type)?;
+            let next_cursor = &pagination.next_cursor;
+
+            quote! {
+                futures::stream::try_unfold(
+                    Some(None),
+                    move |#cursor: Option<Option<#cursor_type>>| {
+                        #(#clones)*
+
+                        async move {
+                            let #cursor = match #cursor {
+                                Some(#cursor) => #cursor,
+                                None => return Ok::<_, anyhow::Error>(None),
+                            };
+
+                            let response = self::#base(client, #(#arguments),*).await?;
+                            #to_value
+                            let next = #next_cursor;
+                            let items: Vec<#item_type> = #items;
+                            let next = if items.is_empty() { None } else { next.map(Some) };
+
+                            Ok(Some((futures::stream::iter(items.into_iter().map(Ok)), next)))
+                        }
+                    },
+                )
+            }
+        }
+        PaginationStyle::Page => {
+            let parameter = pagination_parameter(route, pagination)?;
+            let page = ident(&parameter.ident);
+            let page_type = parse_type(&parameter.owned_type)?;
+            let first_page = Literal::u64_unsuffixed(pagination.first_page);
+
+            quote! {
+                futures::stream::try_unfold(Some(#first_page), move |#page: Option<#page_type>| {
+                    #(#clones)*
+
+                    async move {
+                        let #page = match #page {
+                            Some(#page) => #page,
+                            None => return Ok::<_, anyhow::Error>(None),
+                        };
+
+                        let response = self::#base(client, #(#arguments),*).await?;
+                        #to_value
+                        let items: Vec<#item_type> = #items;
+                        let next = if items.is_empty() { None } else { Some(#page + 1) };
+
+                        Ok(Some((futures::stream::iter(items.into_iter().map(Ok)), next)))
+                    }
+                })
+            }
+        }
+        PaginationStyle::Link => {
+            let method = ident(route.method);
+            let request = generate_request(route, false)?;
+            let response_type = parse_type(&route.response.type_)?;
+
+            quote! {
+                futures::stream::try_unfold(
+                    Some(None),
+                    move |next_page: Option<Option<reqwest::Url>>| {
+                        #(#clones)*
+
+                        async move {
+                            let builder = match next_page {
+                                Some(Some(url)) => client.request_url(Method::#method, url),
+                                Some(None) => {
+                                    #request
+                                    builder
+                                }
+                                None => return Ok::<_, anyhow::Error>(None),
+                            };
+
+                            let response = client.send(builder).await?.error_for_status()?;
+                            let next = __private::next_link(&response);
+                            let response: #response_type = response.json().await?;
+                            #to_value
+                            let items: Vec<#item_type> = #items;
+                            let next = if items.is_empty() { None } else { next.map(Some) };
+
+                            Ok(Some((futures::stream::iter(items.into_iter().map(Ok)), next)))
+                        }
+                    },
+                )
+            }
+        }
+    };
+
+    Ok(Some(quote! {
+        #docs
+        #deprecated
+        pub fn #name<'a>(
+            client: &'a ApiClient,
+            #(#parameters,)*
+            #payload
+        ) -> futures::stream::BoxStream<'a, Result<#item_type>> {
+            let pages = #pages;
+
+            futures::StreamExt::boxed(futures::TryStreamExt::try_flatten(pages))
+        }
+    }))
+}
+
+fn pagination_parameter<'a>(
+    route: &'a Route,
+    pagination: &RoutePagination,
+) -> Result<&'a RouteParameter> {
+    pagination
+        .parameter
+        .and_then(|index| route.parameters.get(index))
+        .ok_or_else(|| anyhow!("unreachable: the cursor or page parameter is always set"))
+}
+
+/// Generates the helper extracting the URL of the next page from the `Link` header.
+pub(crate) fn generate_pagination_helpers() -> TokenStream {
+    quote! {
+        pub(super) fn next_link(response: &reqwest::Response) -> Option<reqwest::Url> {
+            response
+                .headers()
+                .get_all(reqwest::header::LINK)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .find_map(|link| {
+                    let mut parts = link.split(';');
+                    let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+                    let next = parts.any(|part| match part.trim().strip_prefix("rel=") {
+                        Some(rel) => rel.trim_matches('"').split_whitespace().any(|rel| rel == "next"),
+                        None => false,
+                    });
+
+                    // the URL may be relative to the one of the current page
+                    next.then(|| response.url().join(url).ok()).flatten()
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use okapi::openapi3::{Operation, PathItem};
+
+    #[test]
+    fn operations_are_called_by_path() {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let operation: Operation = serde_yaml::from_str(
+            r#"
+operationId: cursor
+parameters:
+  - { name: cursor, in: query, schema: { type: string } }
+x-pagination: { cursor: cursor, next-cursor: next, items: items }
+responses:
+  "200":
+    description: A page
+    content:
+      application/json:
+        schema:
+          type: object
+          properties:
+            items: { type: array, items: { type: string } }
+            next: { type: string }
+"#,
+        )
+        .unwrap();
+        let route = Route::analyze(
+            "/cursor",
+            "GET",
+            &operation,
+            &PathItem::default(),
+            &Components::default(),
+            &mapper,
+        )
+        .unwrap();
+
+        let function = generate_paginated_route(&route)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+        assert!(
+            function.contains("letresponse=self::cursor(client,cursor.as_deref()).await?;"),
+            "{function}"
+        );
+    }
+}
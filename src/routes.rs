@@ -1,522 +1,2308 @@
+use crate::client_config::default_server_url;
+use crate::codegen::{doc, format_file, ident, parse_type, pascal_case, snake_case, unique_name};
+use crate::config::QuerySerialization;
+use crate::pagination::{
+    generate_paginated_route, generate_pagination_helpers, PaginationStyle, RoutePagination,
+};
 use crate::types;
 use crate::types::{
-    map_type, reference_name_to_models_path, resolve, ResolveTarget, ResolvedReference,
+    reference_name_to_models_path, resolve, ResolveTarget, ResolvedReference, TypeMapper,
 };
 use anyhow::{anyhow, bail, Context, Result};
-use convert_case::{Case, Casing};
 use okapi::openapi3::{
-    Components, MediaType, Operation, Parameter, ParameterValue, PathItem, RefOr,
+    Components, Example, ExampleValue, MediaType, Operation, Parameter, ParameterValue, PathItem,
+    RefOr, RequestBody, Response,
 };
 use okapi::Map;
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
 use regex::Regex;
-use schemars::schema::{Schema, SingleOrVec};
-use std::borrow::Borrow;
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+use std::collections::BTreeSet;
 use std::ops::Deref;
-use std::path::Path;
+use syn::UseTree;
 
 pub(crate) fn generate_routes(
     paths: &Map<String, PathItem>,
-    src_path: &Path,
     components: &Components,
-    models: &[String],
-) -> Result<()> {
-    let path = src_path.join("lib.rs");
+    mapper: &TypeMapper,
+) -> Result<String> {
+    let models = generate_models_module(&mapper.config.models)?;
+    let library = mapper.config.name.replace('-', "_");
+    let mut routes = Vec::new();
 
-    // https://stackoverflow.com/a/50691004/11494565
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(path)
-        .context("Failed to open or create lib.rs file")?;
+    for (endpoint, item) in paths {
+        for (method, operation) in operations(item) {
+            routes.push(Route::analyze(
+                endpoint, method, operation, item, components, mapper,
+            )?);
+        }
+    }
 
-    let mut writer = BufWriter::new(file);
+    let mut functions = Vec::new();
 
-    writeln!(writer, "#![forbid(unsafe_code)]")?;
-    writeln!(writer, "#![allow(unused_mut)]")?;
-    writeln!(writer, "#![allow(unused_variables)]")?;
-    writeln!(writer, "#![allow(unused_imports)]\n")?;
+    for route in &routes {
+        functions.push(generate_route(route, &library)?);
+        functions.extend(generate_streaming_route(route)?);
+        functions.push(generate_options_route(route)?);
+        functions.extend(generate_response_route(route)?);
+        functions.extend(generate_paginated_route(route)?);
+    }
 
-    writeln!(writer, "use anyhow::{{Context as _, Result}};")?;
-    writeln!(writer, "use crate::clients::ApiClient;")?;
-    writeln!(writer, "use reqwest::Method;")?;
+    let response_helpers = routes
+        .iter()
+        .any(has_response_variant)
+        .then(generate_response_helpers);
+    let private_module = generate_private_module(&routes);
+    let tests = generate_tests(&routes)?;
+    let types_module = mapper.has_types().then(|| quote! { pub mod types; });
 
-    writeln!(writer, "pub mod builder;")?;
-    writeln!(writer, "pub mod clients;\n")?;
+    format_file(quote! {
+        #![forbid(unsafe_code)]
+        #![allow(unused_mut)]
+        #![allow(unused_variables)]
+        #![allow(unused_imports)]
 
-    writeln!(writer, "pub(crate) mod models {{")?;
+        use anyhow::{Context as _, Result};
+        use clients::{ApiClient, RequestOptions};
+        use reqwest::Method;
 
-    for model in models {
-        writeln!(writer, "    pub(crate) use {model};")?;
-    }
+        pub mod builder;
+        pub mod clients;
+        #types_module
 
-    writeln!(writer, "}}\n")?;
+        #models
 
-    for (endpoint, item) in paths {
-        // this is so ugly omg 😭
-        if let Some(operation) = &item.get {
-            generate_route(
-                endpoint,
-                "GET",
-                operation,
-                &item.parameters,
-                &mut writer,
-                components,
-            )?;
-        }
-        if let Some(operation) = &item.put {
-            generate_route(
-                endpoint,
-                "PUT",
-                operation,
-                &item.parameters,
-                &mut writer,
-                components,
-            )?;
-        }
-        if let Some(operation) = &item.post {
-            generate_route(
-                endpoint,
-                "POST",
-                operation,
-                &item.parameters,
-                &mut writer,
-                components,
-            )?;
-        }
-        if let Some(operation) = &item.delete {
-            generate_route(
-                endpoint,
-                "DELETE",
-                operation,
-                &item.parameters,
-                &mut writer,
-                components,
-            )?;
-        }
-        if let Some(operation) = &item.patch {
-            generate_route(
-                endpoint,
-                "PATCH",
-                operation,
-                &item.parameters,
-                &mut writer,
-                components,
-            )?;
+        #(#functions)*
+        #response_helpers
+        #private_module
+        #tests
+    })
+}
+
+/// Generates the private `models` module, re-exporting all the configured model paths.
+pub(crate) fn generate_models_module(models: &[String]) -> Result<TokenStream> {
+    let models = models
+        .iter()
+        .map(|model| {
+            syn::parse_str::<UseTree>(model)
+                .with_context(|| format!("Failed to parse model path `{model}`"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        pub(crate) mod models {
+            #(pub(crate) use #models;)*
         }
+    })
+}
 
-        // options, head, trace not yet supported
+/// The HTTP methods of the operations returned by [`operations`], in the same order as
+/// [`operations_mut`].
+pub(crate) const METHODS: [&str; 8] = [
+    "GET", "PUT", "POST", "DELETE", "OPTIONS", "HEAD", "PATCH", "TRACE",
+];
 
-        writeln!(writer)?;
-    }
+/// Returns all operations of the path item together with their HTTP method.
+pub(crate) fn operations(item: &PathItem) -> impl Iterator<Item = (&'static str, &Operation)> {
+    METHODS
+        .into_iter()
+        .zip([
+            &item.get,
+            &item.put,
+            &item.post,
+            &item.delete,
+            &item.options,
+            &item.head,
+            &item.patch,
+            &item.trace,
+        ])
+        .filter_map(|(method, operation)| operation.as_ref().map(|operation| (method, operation)))
+}
 
-    writer.flush().context("Failed to flush api.rs")?;
+/// Returns mutable references to all operation slots of the path item.
+pub(crate) fn operations_mut(item: &mut PathItem) -> [&mut Option<Operation>; 8] {
+    [
+        &mut item.get,
+        &mut item.put,
+        &mut item.post,
+        &mut item.delete,
+        &mut item.options,
+        &mut item.head,
+        &mut item.patch,
+        &mut item.trace,
+    ]
+}
 
-    Ok(())
+/// Returns the name of the generated function for the operation: the `x-rust-name` extension if
+/// present, otherwise the (possibly derived) operation ID.
+pub(crate) fn operation_name(endpoint: &str, method: &str, operation: &Operation) -> String {
+    match operation
+        .extensions
+        .get("x-rust-name")
+        .and_then(|name| name.as_str())
+    {
+        Some(name) => snake_case(name),
+        None => snake_case(&operation_id(endpoint, method, operation)),
+    }
 }
 
-fn generate_route(
-    endpoint: &str,
-    method: &str,
-    operation: &Operation,
-    shared_parameters: &[RefOr<Parameter>],
-    writer: &mut BufWriter<File>,
-    components: &Components,
-) -> Result<()> {
-    if let Some(description) = &operation.description {
-        writeln!(writer, "#[doc = r#\"{description}\"#]")?;
+/// Returns the operation ID, or a name derived from the method and path if it's missing
+/// (`GET /workspaces/{workspace_id}/notebooks` becomes `get_workspaces_by_workspace_id_notebooks`).
+pub(crate) fn operation_id(endpoint: &str, method: &str, operation: &Operation) -> String {
+    if let Some(id) = &operation.operation_id {
+        return id.clone();
     }
 
-    let method_name = operation
-        .operation_id
-        .as_ref()
-        .ok_or_else(|| anyhow!("\"{} {}\" does not have operation_id", method, endpoint))?;
+    let mut words = vec![method.to_lowercase()];
+
+    for segment in endpoint.split('/').filter(|segment| !segment.is_empty()) {
+        match segment
+            .strip_prefix('{')
+            .and_then(|segment| segment.strip_suffix('}'))
+        {
+            Some(parameter) => words.extend(["by".to_owned(), parameter.to_owned()]),
+            None => words.push(segment.to_owned()),
+        }
+    }
 
-    writeln!(writer, "pub async fn {method_name}(")?;
-    writeln!(writer, "    client: &ApiClient,")?;
+    words.join("_")
+}
 
-    for raw_param in shared_parameters.iter().chain(&operation.parameters) {
-        match resolve(ResolveTarget::Parameter(&Some(raw_param)), components)? {
-            Some(ResolvedReference::Parameter(parameter)) => {
-                write!(writer, "    {}: ", &parameter.name.to_case(Case::Snake))?;
+/// Names used for variables by the generated code, which parameters can't use.
+const RESERVED_NAMES: &[&str] = &[
+    "args",
+    "body_file",
+    "builder",
+    "client",
+    "command",
+    "content_length",
+    "cookies",
+    "headers",
+    "next_page",
+    "options",
+    "path",
+    "payload",
+    "query",
+    "response",
+    "server",
+];
 
-                if !parameter.required {
-                    write!(writer, "Option<")?;
-                }
+/// The result of analyzing a single operation: its parameters, request body and response,
+/// already mapped to Rust types. Shared between all generation targets.
+pub(crate) struct Route<'a> {
+    pub(crate) endpoint: &'a str,
+    pub(crate) method: &'static str,
+    pub(crate) operation: &'a Operation,
+    /// The snake cased name used for the generated functions
+    pub(crate) name: String,
+    /// The base URL overriding the one of the client, if the path or operation has its own servers
+    pub(crate) server: Option<String>,
+    pub(crate) parameters: Vec<RouteParameter>,
+    pub(crate) body: Option<RouteBody>,
+    /// The status code of the success response: `200` if it's documented or there is none,
+    /// otherwise the lowest documented `2xx` status
+    pub(crate) status: String,
+    pub(crate) response: RouteResponse,
+    /// How to fetch all pages, if the operation has the `x-pagination` extension
+    pub(crate) pagination: Option<RoutePagination>,
+}
 
-                match &parameter.value {
-                    ParameterValue::Schema { schema, .. } => {
-                        let type_ = types::map_type(
-                            schema.format.as_deref(),
-                            schema.instance_type.as_ref(),
-                            schema.reference.as_deref(),
-                            true,
-                        )
-                        .with_context(|| {
-                            format!(
-                                "Failed to map type for parameter {}. Schema: {:?}",
-                                &parameter.name, schema
-                            )
-                        })?;
-
-                        let string: &str = type_.borrow();
-                        write!(writer, "{string}")?;
-                    }
-                    ParameterValue::Content { .. } => {}
-                }
+pub(crate) struct RouteParameter {
+    /// The name as it appears in the OpenAPI document
+    pub(crate) name: String,
+    /// The snake cased name used for the Rust identifier
+    pub(crate) ident: String,
+    /// Where the parameter is located (`path`, `query`, `header` or `cookie`)
+    pub(crate) location: String,
+    pub(crate) required: bool,
+    pub(crate) description: Option<String>,
+    /// The type used when passing the parameter as an argument, may be a reference
+    pub(crate) type_: String,
+    /// The type used when the parameter needs to be owned
+    pub(crate) owned_type: String,
+    /// How the value is serialized when it's passed in the query string
+    pub(crate) query: QuerySerialization,
+    pub(crate) deprecated: bool,
+    pub(crate) example: Option<Value>,
+}
+
+pub(crate) struct RouteBody {
+    pub(crate) kind: BodyKind,
+    /// The type used when passing the body as an argument
+    pub(crate) type_: String,
+    /// The type used when the body needs to be owned
+    pub(crate) owned_type: String,
+    pub(crate) description: Option<String>,
+    pub(crate) example: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BodyKind {
+    Json,
+    Form,
+    Binary,
+}
+
+pub(crate) struct RouteResponse {
+    pub(crate) kind: ResponseType,
+    pub(crate) type_: String,
+    pub(crate) example: Option<Value>,
+    /// The headers documented for the response
+    pub(crate) headers: Vec<ResponseHeader>,
+}
+
+pub(crate) struct ResponseHeader {
+    /// The name as it appears in the OpenAPI document
+    pub(crate) name: String,
+    /// The snake cased name used for the field of the headers struct
+    pub(crate) ident: String,
+    pub(crate) required: bool,
+    pub(crate) description: Option<String>,
+    /// The type the header value is parsed into
+    pub(crate) type_: String,
+}
+
+impl<'a> Route<'a> {
+    pub(crate) fn analyze(
+        endpoint: &'a str,
+        method: &'static str,
+        operation: &'a Operation,
+        item: &PathItem,
+        components: &Components,
+        mapper: &TypeMapper,
+    ) -> Result<Self> {
+        let name = operation_name(endpoint, method, operation);
+        let server = operation
+            .servers
+            .as_ref()
+            .or(item.servers.as_ref())
+            .and_then(|servers| servers.first())
+            .map(default_server_url);
 
-                if !parameter.required {
-                    write!(writer, ">")?;
+        // prefix for the names of the structs generated for inline schemas
+        let prefix = pascal_case(&name);
+
+        let mut parameters = Vec::new();
+        // names used by the generated code next to the parameters
+        let mut taken: BTreeSet<String> =
+            RESERVED_NAMES.iter().map(|&name| name.to_owned()).collect();
+
+        for raw_param in item.parameters.iter().chain(&operation.parameters) {
+            match resolve(ResolveTarget::Parameter(&Some(raw_param)), components)? {
+                Some(ResolvedReference::Parameter(parameter)) => {
+                    parameters.push(analyze_parameter(&parameter, &prefix, &mut taken, mapper)?);
                 }
+                Some(resolved) => bail!(
+                    "resolved to unexpected type {:?}, expected `Parameter`",
+                    resolved
+                ),
+                None => {}
+            }
+        }
+
+        let body = match resolve(
+            ResolveTarget::RequestBody(&operation.request_body.as_ref()),
+            components,
+        )? {
+            Some(ResolvedReference::RequestBody(body)) => {
+                Some(analyze_request_body(&body, &prefix, mapper)?)
+            }
+            Some(resolved) => bail!(
+                "resolved to unexpected type {:?}, expected `RequestBody`",
+                resolved
+            ),
+            None => None,
+        };
 
-                writeln!(writer, ",")?;
+        // `200` if it's documented, otherwise the lowest other success status
+        let responses = &operation.responses.responses;
+        let success = responses.get_key_value("200").or_else(|| {
+            responses
+                .iter()
+                .filter(|(status, _)| status.starts_with('2'))
+                .min_by_key(|(status, _)| status.as_str())
+        });
+        let (status, ok_response) = match success {
+            Some((status, response)) => (status.clone(), Some(response)),
+            None => ("200".to_owned(), None),
+        };
+        let resolved_response = resolve(ResolveTarget::Response(&ok_response), components)?;
+        let mut response = match &resolved_response {
+            // responses to `HEAD` requests never have a body, only the headers are of interest
+            _ if method == "HEAD" => RouteResponse {
+                kind: ResponseType::Headers,
+                type_: "reqwest::header::HeaderMap".to_owned(),
+                example: None,
+                headers: Vec::new(),
+            },
+            Some(ResolvedReference::Responses(response)) => {
+                analyze_response(response, &prefix, mapper)?
             }
             Some(resolved) => bail!(
-                "resolved to unexpected type {:?}, expected `Parameter`",
+                "resolved to unexpected type {:?}, expected `Response`",
                 resolved
             ),
-            None => {}
+            None => RouteResponse {
+                kind: ResponseType::None,
+                type_: "()".to_owned(),
+                example: None,
+                headers: Vec::new(),
+            },
+        };
+
+        if let Some(ResolvedReference::Responses(resolved)) = &resolved_response {
+            if response.kind != ResponseType::Headers {
+                response.headers = analyze_response_headers(resolved, &prefix, components, mapper)?;
+            }
         }
+
+        let pagination = match operation.extensions.get("x-pagination") {
+            Some(extension) => {
+                let schema = match &resolved_response {
+                    Some(ResolvedReference::Responses(response)) => response
+                        .content
+                        .get("application/json")
+                        .and_then(|media| media.schema.as_ref()),
+                    _ => None,
+                };
+
+                Some(
+                    RoutePagination::analyze(
+                        extension,
+                        &parameters,
+                        &response,
+                        schema,
+                        &prefix,
+                        components,
+                        mapper,
+                    )
+                    .with_context(|| format!("Invalid `x-pagination` for `{name}`"))?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            endpoint,
+            method,
+            operation,
+            name,
+            server,
+            parameters,
+            body,
+            status,
+            response,
+            pagination,
+        })
     }
 
-    match resolve(
-        ResolveTarget::RequestBody(&operation.request_body.as_ref()),
-        components,
-    )? {
-        Some(ResolvedReference::RequestBody(body)) => {
-            let media_types: Vec<&MediaType> = body
-                .content
-                .iter()
-                .filter_map(|(content_type, media_type)| {
-                    if content_type == "application/json"
-                        || content_type == "multipart/form-data"
-                        || content_type == "application/octet-stream"
-                    {
-                        Some(media_type)
-                    } else {
-                        eprintln!(
-                            "warn: found \"{content_type}\", expected json, form data or octet stream"
-                        );
-                        None
-                    }
-                })
-                .collect();
-            let json_type = media_types
-                .first()
-                .ok_or_else(|| anyhow!("unknown media type"))?;
-            let schema = json_type
-                .schema
-                .as_ref()
-                .ok_or_else(|| anyhow!("need a schema"))?;
-
-            if let Some(reference) = &schema.reference {
-                let reference = reference_name_to_models_path(reference);
-                writeln!(writer, "    payload: {reference}")?;
-            } else if let Some(array) = &schema.array {
-                let items = array
-                    .items
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("array but no items?"))?;
-
-                match items {
-                    SingleOrVec::Single(schema) => match &**schema {
-                        Schema::Object(object) => {
-                            let type_ = map_type(
-                                object.format.as_deref(),
-                                object.instance_type.as_ref(),
-                                object.reference.as_deref(),
-                                true,
-                            )?;
-
-                            writeln!(writer, "    payload: Vec<{type_}>")?;
-                        }
-                        Schema::Bool(_) => bail!("simple boolean Vec is unsupported"),
-                    },
-                    SingleOrVec::Vec(_) => bail!("Vec with Array as items is not supported"),
+    /// Returns the arguments for calling the generated client function, taken from variables
+    /// named after the parameters (`Option`s for the optional ones) and `payload`, which all hold
+    /// owned values.
+    pub(crate) fn call_arguments(&self) -> Vec<TokenStream> {
+        let mut arguments = Vec::new();
+
+        for parameter in &self.parameters {
+            let ident = ident(&parameter.ident);
+
+            // the client takes some parameters by reference
+            arguments.push(if !parameter.type_.starts_with('&') {
+                quote! { #ident }
+            } else if parameter.required {
+                quote! { &#ident }
+            } else if parameter.owned_type == "String" {
+                quote! { #ident.as_deref() }
+            } else {
+                quote! { #ident.as_ref() }
+            });
+        }
+
+        if let Some(body) = &self.body {
+            arguments.push(if body.type_ == body.owned_type {
+                quote! { payload }
+            } else if body.type_.starts_with('&') {
+                quote! { &payload }
+            } else if body.type_ == "Vec<&str>" {
+                quote! { payload.iter().map(String::as_str).collect() }
+            } else {
+                quote! { payload.iter().collect() }
+            });
+        }
+
+        arguments
+    }
+
+    /// Returns the names of the parameters used within the endpoint template, in order.
+    pub(crate) fn path_arguments(&self) -> Result<Vec<&'a str>> {
+        // https://stackoverflow.com/a/413077/11494565
+        let regex = Regex::new(r#"\{(.*?)\}"#).context("Failed to build regex")?;
+        let mut arguments = vec![];
+
+        for captures in regex.captures_iter(self.endpoint) {
+            let capture = captures
+                .get(1)
+                .ok_or_else(|| anyhow!("unreachable: always two capture groups (0 + 1)"))?;
+
+            arguments.push(capture.as_str());
+        }
+
+        Ok(arguments)
+    }
+}
+
+fn analyze_parameter(
+    parameter: &Parameter,
+    prefix: &str,
+    taken: &mut BTreeSet<String>,
+    mapper: &TypeMapper,
+) -> Result<RouteParameter> {
+    let (type_, owned_type, query, example) = match &parameter.value {
+        ParameterValue::Schema {
+            schema,
+            example: parameter_example,
+            examples,
+            ..
+        } => {
+            let map = |as_ref| {
+                mapper
+                    .map_type(
+                        schema,
+                        &format!("{prefix}{}", pascal_case(&parameter.name)),
+                        as_ref,
+                    )
+                    .with_context(|| {
+                        format!(
+                            "Failed to map type for parameter {}. Schema: {:?}",
+                            &parameter.name, schema
+                        )
+                    })
+            };
+
+            let query = match types::find_mapping(schema, mapper.config) {
+                Some(mapping) => mapping.query,
+                // maps can't be represented in a query string otherwise. `has_type` can't be used,
+                // as it's also true for references and schemas without a type
+                None if matches!(
+                    &schema.instance_type,
+                    Some(types) if types.contains(&InstanceType::Object)
+                ) =>
+                {
+                    QuerySerialization::Json
                 }
+                None => QuerySerialization::Serde,
+            };
+
+            (
+                map(true)?.into_owned(),
+                map(false)?.into_owned(),
+                query,
+                example(parameter_example.as_ref(), examples, schema),
+            )
+        }
+        ParameterValue::Content { .. } => (
+            String::new(),
+            String::new(),
+            QuerySerialization::Serde,
+            None,
+        ),
+    };
+
+    Ok(RouteParameter {
+        name: parameter.name.clone(),
+        ident: unique_name(snake_case(&parameter.name), taken),
+        location: parameter.location.clone(),
+        required: parameter.required,
+        description: parameter.description.clone(),
+        type_,
+        owned_type,
+        query,
+        deprecated: parameter.deprecated,
+        example,
+    })
+}
+
+/// Returns the example given for a parameter or media type, falling back to the examples of the
+/// schema itself.
+fn example(
+    example: Option<&Value>,
+    examples: &Option<Map<String, Example>>,
+    schema: &SchemaObject,
+) -> Option<Value> {
+    let named_examples = examples
+        .iter()
+        .flat_map(|examples| examples.values())
+        .filter_map(|example| match &example.value {
+            ExampleValue::Value(value) => Some(value),
+            ExampleValue::ExternalValue(_) => None,
+        });
+    // OpenAPI 3.0 uses `example` on schemas, which schemars doesn't know about
+    let schema_examples = schema.extensions.get("example").into_iter().chain(
+        schema
+            .metadata
+            .iter()
+            .flat_map(|metadata| &metadata.examples),
+    );
+
+    example
+        .into_iter()
+        .chain(named_examples)
+        .chain(schema_examples)
+        .next()
+        .cloned()
+}
+
+fn analyze_request_body(
+    body: &RequestBody,
+    prefix: &str,
+    mapper: &TypeMapper,
+) -> Result<RouteBody> {
+    let media_types: Vec<&MediaType> = body
+        .content
+        .iter()
+        .filter_map(|(content_type, media_type)| {
+            if content_type == "application/json"
+                || content_type == "multipart/form-data"
+                || content_type == "application/octet-stream"
+            {
+                Some(media_type)
             } else {
-                // inline type
-                let type_ = map_type(
-                    schema.format.as_deref(),
-                    schema.instance_type.as_ref(),
-                    schema.reference.as_deref(),
-                    true,
-                )?;
-
-                writeln!(writer, "    payload: {type_},")?;
+                eprintln!(
+                    "warn: found \"{content_type}\", expected json, form data or octet stream"
+                );
+                None
             }
+        })
+        .collect();
+    let json_type = media_types
+        .first()
+        .ok_or_else(|| anyhow!("unknown media type"))?;
+    let schema = json_type
+        .schema
+        .as_ref()
+        .ok_or_else(|| anyhow!("need a schema"))?;
+
+    let (type_, owned_type) = if let Some(reference) = &schema.reference {
+        let reference = reference_name_to_models_path(reference);
+        (reference.clone(), reference)
+    } else if let Some(array) = &schema.array {
+        let items = array
+            .items
+            .as_ref()
+            .ok_or_else(|| anyhow!("array but no items?"))?;
+
+        match items {
+            SingleOrVec::Single(schema) => match &**schema {
+                Schema::Object(object) => {
+                    let map =
+                        |as_ref| mapper.map_type(object, &format!("{prefix}RequestItem"), as_ref);
+
+                    (
+                        format!("Vec<{}>", map(true)?),
+                        format!("Vec<{}>", map(false)?),
+                    )
+                }
+                Schema::Bool(_) => bail!("simple boolean Vec is unsupported"),
+            },
+            SingleOrVec::Vec(_) => bail!("Vec with Array as items is not supported"),
         }
-        Some(resolved) => bail!(
-            "resolved to unexpected type {:?}, expected `RequestBody`",
-            resolved
-        ),
-        None => {}
-    }
-
-    write!(writer, ") -> Result<")?;
-
-    let mut response_type = None;
-
-    match resolve(
-        ResolveTarget::Response(&operation.responses.responses.get("200")),
-        components,
-    )? {
-        Some(ResolvedReference::Responses(response)) => {
-            if response.content.is_empty() {
-                response_type = Some(ResponseType::None);
-                write!(writer, "()")?;
-            } else if let Some(json_media) = response.content.get("application/json") {
-                let schema = json_media
-                    .schema
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("need a schema"))?;
-
-                if let Some(reference) = &schema.reference {
-                    if let Some((_, reference_name)) = reference.rsplit_once('/') {
-                        write!(writer, "models::{}", reference_name.to_case(Case::Pascal))?;
-                    } else {
-                        write!(writer, "models::{}", reference.to_case(Case::Pascal))?;
-                    }
-                } else if let Some(array) = &schema.array {
-                    match &array.items {
-                        Some(SingleOrVec::Single(single)) => match single.deref() {
-                            Schema::Bool(_) => eprintln!("unsupported bool for array items"),
-                            Schema::Object(schema) => {
-                                let type_ = map_type(
-                                    schema.format.as_deref(),
-                                    schema.instance_type.as_ref(),
-                                    schema.reference.as_deref(),
-                                    false,
-                                )?;
-                                write!(writer, "Vec<{type_}>")?;
-                            }
-                        },
-                        Some(SingleOrVec::Vec(vec)) => {
-                            eprintln!("unsupported multiple items vec {vec:?}")
-                        }
-                        None => eprintln!("type is array but has no items? {schema:?}"),
+    } else {
+        // inline type
+        let map = |as_ref| mapper.map_type(schema, &format!("{prefix}Request"), as_ref);
+
+        (map(true)?.into_owned(), map(false)?.into_owned())
+    };
+
+    let kind = if body.content.contains_key("application/json") {
+        BodyKind::Json
+    } else if body.content.contains_key("multipart/form-data") {
+        BodyKind::Form
+    } else if body.content.contains_key("application/octet-stream") {
+        BodyKind::Binary
+    } else {
+        bail!("Unsupported type(s): {:?}", body.content);
+    };
+
+    Ok(RouteBody {
+        kind,
+        type_,
+        owned_type,
+        description: body.description.clone(),
+        example: example(json_type.example.as_ref(), &json_type.examples, schema),
+    })
+}
+
+pub(crate) fn analyze_response(
+    response: &Response,
+    prefix: &str,
+    mapper: &TypeMapper,
+) -> Result<RouteResponse> {
+    if response.content.is_empty() {
+        return Ok(RouteResponse {
+            kind: ResponseType::None,
+            type_: "()".to_owned(),
+            example: None,
+            headers: Vec::new(),
+        });
+    }
+
+    if let Some(json_media) = response.content.get("application/json") {
+        let schema = json_media
+            .schema
+            .as_ref()
+            .ok_or_else(|| anyhow!("need a schema"))?;
+
+        let type_ = if let Some(reference) = &schema.reference {
+            reference_name_to_models_path(reference)
+        } else if let Some(array) = &schema.array {
+            match &array.items {
+                Some(SingleOrVec::Single(single)) => match single.deref() {
+                    Schema::Bool(_) => {
+                        eprintln!("unsupported bool for array items");
+                        "Vec<serde_json::Value>".to_owned()
                     }
-                } else {
-                    let type_ = map_type(
-                        schema.format.as_deref(),
-                        schema.instance_type.as_ref(),
-                        schema.reference.as_deref(),
-                        false,
-                    )?;
-
-                    if type_ == "()" {
-                        response_type = Some(ResponseType::None);
+                    Schema::Object(schema) => {
+                        let type_ =
+                            mapper.map_type(schema, &format!("{prefix}ResponseItem"), false)?;
+                        format!("Vec<{type_}>")
                     }
-
-                    write!(writer, "{type_}")?;
+                },
+                Some(SingleOrVec::Vec(vec)) => {
+                    eprintln!("unsupported multiple items vec {vec:?}");
+                    "Vec<serde_json::Value>".to_owned()
                 }
-
-                if response_type.is_none() {
-                    response_type = Some(ResponseType::Json);
+                None => {
+                    eprintln!("type is array but has no items? {schema:?}");
+                    "Vec<serde_json::Value>".to_owned()
                 }
-            } else if response.content.get("text/plain").is_some() {
-                response_type = Some(ResponseType::Text);
-                write!(writer, "String")?;
+            }
+        } else {
+            let type_ = mapper.map_type(schema, &format!("{prefix}Response"), false)?;
+
+            if type_ == "()" {
+                return Ok(RouteResponse {
+                    kind: ResponseType::None,
+                    type_: "()".to_owned(),
+                    example: None,
+                    headers: Vec::new(),
+                });
+            }
+
+            if types::is_nullable(schema) {
+                format!("Option<{type_}>")
             } else {
-                // octet-stream should be `bytes::Bytes` so don't warn about it when we reach this fallback
-                if response.content.get("application/octet-stream").is_none() {
-                    let keys: Vec<_> = response.content.keys().collect();
-                    eprintln!(
-                        "warn: unknown response mime type(s), falling back to `bytes::Bytes`: {keys:?}"
-                    );
-                }
+                type_.into_owned()
+            }
+        };
+
+        Ok(RouteResponse {
+            kind: ResponseType::Json,
+            type_,
+            example: example(json_media.example.as_ref(), &json_media.examples, schema),
+            headers: Vec::new(),
+        })
+    } else if let Some((kind, media)) = stream_media_type(response) {
+        let type_ = match &media.schema {
+            Some(schema) => mapper
+                .map_type(schema, &format!("{prefix}ResponseItem"), false)?
+                .into_owned(),
+            None => "serde_json::Value".to_owned(),
+        };
+
+        Ok(RouteResponse {
+            kind,
+            type_,
+            example: media
+                .schema
+                .as_ref()
+                .and_then(|schema| example(media.example.as_ref(), &media.examples, schema)),
+            headers: Vec::new(),
+        })
+    } else if response.content.contains_key("text/plain") {
+        Ok(RouteResponse {
+            kind: ResponseType::Text,
+            type_: "String".to_owned(),
+            example: None,
+            headers: Vec::new(),
+        })
+    } else {
+        // octet-stream should be `bytes::Bytes` so don't warn about it when we reach this fallback
+        if !response.content.contains_key("application/octet-stream") {
+            let keys: Vec<_> = response.content.keys().collect();
+            eprintln!(
+                "warn: unknown response mime type(s), falling back to `bytes::Bytes`: {keys:?}"
+            );
+        }
+
+        Ok(RouteResponse {
+            kind: ResponseType::Bytes,
+            type_: "bytes::Bytes".to_owned(),
+            example: None,
+            headers: Vec::new(),
+        })
+    }
+}
+
+/// Types that the generated code can parse header values into, using their `FromStr`
+/// implementation.
+const HEADER_TYPES: &[&str] = &[
+    "String",
+    "bool",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "f32",
+    "f64",
+    "uuid::Uuid",
+    "url::Url",
+    "std::net::IpAddr",
+    "std::net::Ipv4Addr",
+    "std::net::Ipv6Addr",
+    "types::Base64",
+];
+
+fn analyze_response_headers(
+    response: &Response,
+    prefix: &str,
+    components: &Components,
+    mapper: &TypeMapper,
+) -> Result<Vec<ResponseHeader>> {
+    let mut headers = Vec::new();
+    let mut taken = BTreeSet::new();
+
+    for (name, header) in &response.headers {
+        let header = match header {
+            RefOr::Object(header) => header,
+            RefOr::Ref(reference) => reference
+                .reference
+                .strip_prefix("#/components/headers/")
+                .and_then(|name| components.headers.get(name))
+                .and_then(|header| match header {
+                    RefOr::Object(header) => Some(header),
+                    RefOr::Ref(_) => None,
+                })
+                .ok_or_else(|| anyhow!("Failed to resolve header {}", reference.reference))?,
+        };
+
+        let type_ = match &header.value {
+            ParameterValue::Schema { schema, .. } => mapper
+                .map_type(
+                    schema,
+                    &format!("{prefix}{}Header", pascal_case(name)),
+                    false,
+                )?
+                .into_owned(),
+            ParameterValue::Content { .. } => "String".to_owned(),
+        };
+
+        // anything else is kept as the raw string
+        let type_ = if HEADER_TYPES.contains(&type_.as_str()) {
+            type_
+        } else {
+            eprintln!("warn: header `{name}` of type `{type_}` is returned as `String`");
+            "String".to_owned()
+        };
+
+        headers.push(ResponseHeader {
+            name: name.clone(),
+            ident: unique_name(snake_case(name), &mut taken),
+            required: header.required,
+            description: header.description.clone(),
+            type_,
+        });
+    }
+
+    Ok(headers)
+}
 
-                write!(writer, "bytes::Bytes")?;
+/// Generates the documentation for the functions of the route: the summary and description,
+/// followed by the arguments and the documented error responses.
+pub(crate) fn generate_docs(route: &Route, example: Option<String>) -> TokenStream {
+    let operation = route.operation;
+    let mut sections: Vec<String> = operation
+        .summary
+        .iter()
+        .chain(&operation.description)
+        .map(|text| text.trim().to_owned())
+        .collect();
+
+    if let Some(external_docs) = &operation.external_docs {
+        let text = external_docs
+            .description
+            .as_deref()
+            .unwrap_or("External documentation");
+        sections.push(format!(
+            "See also: [{}]({})",
+            text.trim(),
+            external_docs.url
+        ));
+    }
+
+    let mut arguments: Vec<String> = route
+        .parameters
+        .iter()
+        .map(|parameter| {
+            argument_doc(
+                &parameter.ident,
+                parameter.description.as_deref(),
+                parameter.deprecated,
+            )
+        })
+        .collect();
+
+    if let Some(body) = &route.body {
+        arguments.push(argument_doc("payload", body.description.as_deref(), false));
+    }
+
+    if !arguments.is_empty() {
+        sections.push(format!("# Arguments\n\n{}", arguments.join("\n")));
+    }
+
+    let errors: Vec<String> = operation
+        .responses
+        .responses
+        .iter()
+        .filter(|(status, _)| !status.starts_with('2'))
+        .map(|(status, response)| (format!("`{status}`"), response))
+        .chain(
+            operation
+                .responses
+                .default
+                .iter()
+                .map(|response| ("Other status codes".to_owned(), response)),
+        )
+        .map(|(name, response)| match response {
+            RefOr::Object(response) if !response.description.trim().is_empty() => {
+                list_item(&name, &response.description)
             }
+            _ => format!("* {name}"),
+        })
+        .collect();
+
+    if !errors.is_empty() {
+        sections.push(format!(
+            "# Errors\n\nDocumented error responses:\n\n{}",
+            errors.join("\n")
+        ));
+    }
+
+    sections.extend(example);
+
+    let deprecated = operation.deprecated.then(|| quote! { #[deprecated] });
+    let docs = doc(&sections.join("\n\n"));
+
+    quote! {
+        #docs
+        #deprecated
+    }
+}
+
+fn argument_doc(ident: &str, description: Option<&str>, deprecated: bool) -> String {
+    let name = format!("`{}`", ident.trim_start_matches("r#"));
+    let description = match (description, deprecated) {
+        (Some(description), true) => format!("**Deprecated.** {}", description.trim()),
+        (Some(description), false) => description.trim().to_owned(),
+        (None, true) => "**Deprecated.**".to_owned(),
+        (None, false) => return format!("* {name}"),
+    };
+
+    list_item(&name, &description)
+}
+
+/// Formats a Markdown list item, indenting the following lines so they stay part of the item.
+fn list_item(name: &str, description: &str) -> String {
+    format!("* {name} - {}", description.trim().replace('\n', "\n  "))
+}
+
+/// Returns the media type of a response that consists of a stream of items.
+fn stream_media_type(response: &Response) -> Option<(ResponseType, &MediaType)> {
+    [
+        ("text/event-stream", ResponseType::EventStream),
+        ("application/x-ndjson", ResponseType::JsonLines),
+    ]
+    .into_iter()
+    .find_map(|(content_type, kind)| {
+        response
+            .content
+            .get(content_type)
+            .map(|media_type| (kind, media_type))
+    })
+}
+
+fn generate_route(route: &Route, library: &str) -> Result<TokenStream> {
+    let docs = generate_docs(route, generate_example(route, library)?);
+    let name = ident(&route.name);
+    let parameters = function_parameters(route)?;
+
+    let payload = match &route.body {
+        Some(body) => {
+            let type_ = parse_type(&body.type_)?;
+            Some(quote! { payload: #type_ })
         }
-        Some(resolved) => bail!(
-            "resolved to unexpected type {:?}, expected `Response`",
-            resolved
-        ),
-        None => {
-            write!(writer, "()")?;
-            response_type = Some(ResponseType::None);
+        None => None,
+    };
+
+    let response = parse_type(&route.response.type_)?;
+    let response = if route.response.kind.is_stream() {
+        quote! { futures::stream::BoxStream<'static, Result<#response>> }
+    } else {
+        quote! { #response }
+    };
+    let body = generate_function_body(route, false)?;
+
+    Ok(quote! {
+        #docs
+        pub async fn #name(
+            client: &ApiClient,
+            #(#parameters,)*
+            #payload
+        ) -> Result<#response> {
+            #body
         }
+    })
+}
+
+/// Generates the `_stream` variant of routes with binary request or response bodies, which
+/// streams them instead of buffering them in memory.
+fn generate_streaming_route(route: &Route) -> Result<Option<TokenStream>> {
+    let upload = matches!(&route.body, Some(body) if body.kind == BodyKind::Binary);
+    let download = route.response.kind == ResponseType::Bytes;
+
+    let text = match (upload, download) {
+        (false, false) => return Ok(None),
+        (true, false) => "sends the request body as it's read",
+        (false, true) => "returns the response body as a stream of chunks",
+        (true, true) => {
+            "sends the request body as it's read\nand returns the response body as a stream of chunks"
+        }
+    };
+
+    let mut text = format!(
+        "Like [`{}`], but {text}\ninstead of buffering it in memory.",
+        route.name.trim_start_matches("r#")
+    );
+
+    if upload {
+        text.push_str(
+            "\n\nThe `payload` can be created from a stream or a file, see [`reqwest::Body`].\n\
+            `content_length` is sent as the `Content-Length` header when it's known up front.",
+        );
     }
 
-    // RETURN TYPE
+    let docs = doc(&text);
+    let deprecated = route.operation.deprecated.then(|| quote! { #[deprecated] });
+    let name = ident(&format!("{}_stream", route.name.trim_start_matches("r#")));
+    let parameters = function_parameters(route)?;
 
-    writeln!(writer, "> {{")?;
+    let payload = match &route.body {
+        Some(_) if upload => Some(quote! { payload: reqwest::Body, content_length: Option<u64> }),
+        Some(body) => {
+            let type_ = parse_type(&body.type_)?;
+            Some(quote! { payload: #type_ })
+        }
+        None => None,
+    };
 
-    generate_function_body(
-        endpoint,
-        method,
-        operation,
-        writer,
-        components,
-        response_type,
-    )?;
+    let response = if download {
+        quote! { futures::stream::BoxStream<'static, Result<bytes::Bytes>> }
+    } else {
+        let type_ = parse_type(&route.response.type_)?;
 
-    writeln!(writer, "\n}}\n")?;
+        if route.response.kind.is_stream() {
+            quote! { futures::stream::BoxStream<'static, Result<#type_>> }
+        } else {
+            quote! { #type_ }
+        }
+    };
+    let body = generate_function_body(route, true)?;
 
-    Ok(())
+    Ok(Some(quote! {
+        #docs
+        #deprecated
+        pub async fn #name(
+            client: &ApiClient,
+            #(#parameters,)*
+            #payload
+        ) -> Result<#response> {
+            #body
+        }
+    }))
 }
 
-fn generate_function_body(
-    endpoint: &str,
-    method: &str,
-    operation: &Operation,
-    writer: &mut BufWriter<File>,
-    components: &Components,
-    response_type: Option<ResponseType>,
-) -> Result<()> {
-    writeln!(writer, "    let mut builder = client.request(",)?;
-    writeln!(writer, "        Method::{method},")?;
+/// Generates the `_with_options` variant of a route, which applies the given options to the
+/// request.
+fn generate_options_route(route: &Route) -> Result<TokenStream> {
+    let function = route.name.trim_start_matches("r#");
+    let docs = doc(&format!(
+        "Like [`{function}`], but applies the given options to the request.\n\n\
+        The options can be applied to the other variants with [`ApiClient::with_options`]."
+    ));
+    // calls the deprecated function of the operation itself
+    let deprecated = route
+        .operation
+        .deprecated
+        .then(|| quote! { #[deprecated] #[allow(deprecated)] });
+    // called by its path, as a parameter can have the same name
+    let base = ident(&route.name);
+    let name = ident(&format!("{function}_with_options"));
+    let parameters = function_parameters(route)?;
 
-    // https://stackoverflow.com/a/413077/11494565
-    let regex = Regex::new(r#"\{(.*?)\}"#).context("Failed to build regex")?;
-    let mut arguments = vec![];
+    let payload = match &route.body {
+        Some(body) => {
+            let type_ = parse_type(&body.type_)?;
+            Some(quote! { payload: #type_, })
+        }
+        None => None,
+    };
 
-    for captures in regex.captures_iter(endpoint) {
-        let capture = captures
-            .get(1)
-            .ok_or_else(|| anyhow!("unreachable: always two capture groups (0 + 1)"))?;
+    let mut arguments: Vec<_> = route
+        .parameters
+        .iter()
+        .map(|parameter| ident(&parameter.ident))
+        .collect();
 
-        arguments.push(capture.as_str());
+    if route.body.is_some() {
+        arguments.push(ident("payload"));
     }
 
-    if !arguments.is_empty() {
-        write!(writer, "        &format!(\"{endpoint}\", ")?;
+    let response = parse_type(&route.response.type_)?;
+    let response = if route.response.kind.is_stream() {
+        quote! { futures::stream::BoxStream<'static, Result<#response>> }
+    } else {
+        quote! { #response }
+    };
+
+    Ok(quote! {
+        #docs
+        #deprecated
+        pub async fn #name(
+            client: &ApiClient,
+            #(#parameters,)*
+            #payload
+            options: RequestOptions,
+        ) -> Result<#response> {
+            self::#base(&client.with_options(options), #(#arguments),*).await
+        }
+    })
+}
+
+/// Whether the route gets a `_with_response` variant, which is the case for all routes returning
+/// a body or nothing at all.
+fn has_response_variant(route: &Route) -> bool {
+    !route.response.kind.is_stream() && route.response.kind != ResponseType::Headers
+}
+
+/// Generates the `_with_response` variant of a route, which returns the status code and the
+/// documented headers next to the body, together with the struct holding the headers.
+fn generate_response_route(route: &Route) -> Result<Option<TokenStream>> {
+    if !has_response_variant(route) {
+        return Ok(None);
+    }
+
+    let function = route.name.trim_start_matches("r#");
+    let docs = doc(&format!(
+        "Like [`{function}`], but also returns the status code and the documented headers\n\
+        of the response."
+    ));
+    let deprecated = route.operation.deprecated.then(|| quote! { #[deprecated] });
+    let name = ident(&format!("{function}_with_response"));
+    let parameters = function_parameters(route)?;
 
-        for arg in arguments {
-            write!(writer, "{} = {}, ", arg, arg.to_case(Case::Snake))?;
+    let payload = match &route.body {
+        Some(body) => {
+            let type_ = parse_type(&body.type_)?;
+            Some(quote! { payload: #type_ })
         }
+        None => None,
+    };
 
-        write!(writer, ")")?;
+    let (headers_struct, headers_type, headers) = if route.response.headers.is_empty() {
+        (None, quote! { () }, quote! { () })
     } else {
-        write!(writer, "        \"{endpoint}\"")?;
+        let headers_name = ident(&format!("{}ResponseHeaders", pascal_case(&route.name)));
+        let struct_docs = doc(&format!(
+            "The documented headers of the [`{function}`] response."
+        ));
+
+        let mut fields = Vec::new();
+        let mut values = Vec::new();
+
+        for header in &route.response.headers {
+            let field = ident(&header.ident);
+            let name = &header.name;
+            let type_ = parse_type(&header.type_)?;
+            let docs = header.description.as_deref().map(doc);
+
+            if header.required {
+                let missing = format!("Missing `{name}` header");
+
+                fields.push(quote! { #docs pub #field: #type_ });
+                values.push(quote! {
+                    #field: __private::header_value(response.headers(), #name)?.context(#missing)?
+                });
+            } else {
+                fields.push(quote! { #docs pub #field: Option<#type_> });
+                values.push(quote! { #field: __private::header_value(response.headers(), #name)? });
+            }
+        }
+
+        (
+            Some(quote! {
+                #struct_docs
+                #[derive(Clone, Debug)]
+                pub struct #headers_name {
+                    #(#fields,)*
+                }
+            }),
+            quote! { #headers_name },
+            quote! { #headers_name { #(#values,)* } },
+        )
+    };
+
+    let body_type = parse_type(&route.response.type_)?;
+    let body = match route.response.kind {
+        ResponseType::Json => quote! { response.json().await? },
+        ResponseType::Text => quote! { response.text().await? },
+        ResponseType::Bytes => quote! { response.bytes().await? },
+        _ => quote! { () },
+    };
+    let request = generate_request(route, false)?;
+
+    Ok(Some(quote! {
+        #headers_struct
+
+        #docs
+        #deprecated
+        pub async fn #name(
+            client: &ApiClient,
+            #(#parameters,)*
+            #payload
+        ) -> Result<ApiResponse<#body_type, #headers_type>> {
+            #request
+
+            let response = client.send(builder).await?.error_for_status()?;
+            let status = response.status();
+            let headers = #headers;
+            let body = #body;
+
+            Ok(ApiResponse {
+                status,
+                headers,
+                body,
+            })
+        }
+    }))
+}
+
+/// Generates the `ApiResponse` returned by the `_with_response` functions.
+fn generate_response_helpers() -> TokenStream {
+    quote! {
+        /// A response together with its status code and the headers documented for the operation.
+        #[derive(Clone, Debug)]
+        pub struct ApiResponse<T, H = ()> {
+            pub status: reqwest::StatusCode,
+            pub headers: H,
+            pub body: T,
+        }
     }
+}
 
-    writeln!(writer, "\n    )?;")?;
+/// Generates the `__private` module with the helper functions used by the generated functions,
+/// if any of them are used. Keeping them in a module of their own means they can't collide with
+/// the functions generated for operations.
+fn generate_private_module(routes: &[Route]) -> Option<TokenStream> {
+    let mut helpers = Vec::new();
 
-    // Query strings as parameters
-    for ref_or in &operation.parameters {
-        let option = Some(ref_or);
-        let option = resolve(ResolveTarget::Parameter(&option), components)?;
+    let has_kind = |kind| routes.iter().any(|route| route.response.kind == kind);
+    let event_stream = has_kind(ResponseType::EventStream);
+    let json_lines = has_kind(ResponseType::JsonLines);
 
-        if let Some(ResolvedReference::Parameter(parameter)) = option {
-            match parameter.location.as_str() {
-                "path" => continue,
-                "query" => {
-                    let mut parameter_name = parameter.name.to_case(Case::Snake);
+    if event_stream || json_lines {
+        helpers.push(generate_stream_helpers(event_stream, json_lines));
+    }
 
-                    if !parameter.required {
-                        writeln!(
-                            writer,
-                            "    if let Some({parameter_name}) = {parameter_name} {{",
-                        )?;
-                    }
+    if routes
+        .iter()
+        .any(|route| has_response_variant(route) && !route.response.headers.is_empty())
+    {
+        helpers.push(generate_header_helper());
+    }
 
-                    if let ParameterValue::Schema { schema, .. } = &parameter.value {
-                        let type_ = map_type(
-                            schema.format.as_deref(),
-                            schema.instance_type.as_ref(),
-                            schema.reference.as_deref(),
-                            true,
-                        )
-                        .with_context(|| {
-                            format!(
-                                "Failed to map type for parameter {}. Schema: {:?}",
-                                &parameter.name, schema
-                            )
-                        })?;
-
-                        // special handling for types that need to be converted to strings
-                        if type_ == "fiberplane_models::timestamps::Timestamp" {
-                            parameter_name = format!("{parameter_name}.to_string()")
-                        } else if type_ == "std::collections::HashMap<String, String>" {
-                            parameter_name = format!("serde_json::to_string(&{parameter_name})?")
-                        }
-                    }
+    if routes.iter().any(|route| {
+        matches!(&route.pagination, Some(pagination) if pagination.style == PaginationStyle::Link)
+    }) {
+        helpers.push(generate_pagination_helpers());
+    }
 
-                    writeln!(
-                        writer,
-                        "        builder = builder.query(&[(\"{}\", {})]);",
-                        parameter.name, parameter_name
-                    )?;
+    (!helpers.is_empty()).then(|| {
+        quote! {
+            mod __private {
+                use anyhow::{Context as _, Result};
 
-                    if !parameter.required {
-                        writeln!(writer, "    }}")?;
-                    }
+                #(#helpers)*
+            }
+        }
+    })
+}
+
+/// Generates the helper parsing the documented headers of a response.
+fn generate_header_helper() -> TokenStream {
+    quote! {
+        pub(super) fn header_value<T>(headers: &reqwest::header::HeaderMap, name: &str) -> Result<Option<T>>
+        where
+            T: std::str::FromStr,
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            match headers.get(name) {
+                Some(value) => {
+                    let value = value
+                        .to_str()
+                        .with_context(|| format!("Invalid `{name}` header"))?
+                        .parse()
+                        .with_context(|| format!("Invalid `{name}` header"))?;
+
+                    Ok(Some(value))
                 }
-                location => eprintln!("unknown `in`: {location}"),
+                None => Ok(None),
             }
         }
     }
+}
 
-    // Request body
-    if let Some(request_body) = &operation.request_body {
-        match resolve(ResolveTarget::RequestBody(&Some(request_body)), components)? {
-            Some(ResolvedReference::RequestBody(body)) => {
-                if body.content.get("application/json").is_some() {
-                    writeln!(writer, "    builder = builder.json(&payload);")?;
-                } else if body.content.get("multipart/form-data").is_some() {
-                    writeln!(writer, "    builder = builder.form(&payload);")?;
-                } else if body.content.get("application/octet-stream").is_some() {
-                    writeln!(writer, "    builder = builder.body(payload);")?;
-                } else {
-                    eprintln!("Unsupported type(s): {:?}", body.content);
-                }
+pub(crate) fn function_parameters(route: &Route) -> Result<Vec<TokenStream>> {
+    route
+        .parameters
+        .iter()
+        .map(|parameter| {
+            let ident = ident(&parameter.ident);
+            let type_ = parse_type(&parameter.type_)?;
+
+            Ok(if parameter.required {
+                quote! { #ident: #type_ }
+            } else {
+                quote! { #ident: Option<#type_> }
+            })
+        })
+        .collect()
+}
+
+/// Generates a doctest calling the function with the examples from the document, as long as
+/// there are examples for all required arguments.
+fn generate_example(route: &Route, library: &str) -> Result<Option<String>> {
+    // doctests are compiled outside of the crate, where the `models` module isn't visible, so
+    // those types are inferred instead
+    let models = Regex::new(r"\bmodels::\w+").context("Failed to build regex")?;
+    let external_type = |type_: &str| {
+        let type_ = models.replace_all(type_, "_");
+        parse_type(&type_.replace("types::", &format!("{library}::types::")))
+    };
+
+    let library = ident(library);
+    let function = ident(&route.name);
+    let mut variables = Vec::new();
+
+    for parameter in &route.parameters {
+        let ident = ident(&parameter.ident);
+        let type_ = external_type(&parameter.owned_type)?;
+        let type_ = if parameter.required {
+            quote! { #type_ }
+        } else {
+            quote! { Option<#type_> }
+        };
+
+        variables.push(match (&parameter.example, parameter.required) {
+            (Some(example), _) => match json_tokens(example) {
+                Some(value) => quote! {
+                    let #ident: #type_ = serde_json::from_value(serde_json::json!(#value))?;
+                },
+                None => return Ok(None),
+            },
+            (None, false) => quote! { let #ident: #type_ = None; },
+            (None, true) => return Ok(None),
+        });
+    }
+
+    if let Some(body) = &route.body {
+        let type_ = external_type(&body.owned_type)?;
+
+        variables.push(
+            match (body.kind, body.example.as_ref().and_then(json_tokens)) {
+                (BodyKind::Binary, _) => quote! { let payload: #type_ = Default::default(); },
+                (_, Some(value)) => quote! {
+                    let payload: #type_ = serde_json::from_value(serde_json::json!(#value))?;
+                },
+                (_, None) => return Ok(None),
+            },
+        );
+    }
+
+    let arguments = route.call_arguments();
+    let call = quote! { #library::#function(&client, #(#arguments),*).await? };
+    let call = match route.response.kind {
+        ResponseType::None => quote! { #call; },
+        _ => quote! { let response = #call; },
+    };
+
+    let code = format_file(quote! {
+        async fn example() -> anyhow::Result<()> {
+            let client = #library::clients::ApiClient::builder("https://api.example.com".parse()?)
+                .build()?;
+            #(#variables)*
+            #call
+            Ok(())
+        }
+    })?;
+
+    // the surrounding function is only needed to compile the example, so it's hidden
+    let mut lines = vec![
+        "# Example".to_owned(),
+        String::new(),
+        "```no_run".to_owned(),
+    ];
+
+    for line in code.trim_end().lines() {
+        let line = line.strip_prefix("    ").unwrap_or(line);
+
+        lines.push(match line {
+            "Ok(())" | "}" => format!("# {line}"),
+            line if line.starts_with("async fn example()") => format!("# {line}"),
+            line => line.to_owned(),
+        });
+    }
+
+    lines.push("```".to_owned());
+
+    Ok(Some(lines.join("\n")))
+}
+
+/// Generates unit tests checking that the examples of the request bodies and responses can be
+/// deserialized into the generated types and serialized again.
+fn generate_tests(routes: &[Route]) -> Result<Option<TokenStream>> {
+    let mut tests = Vec::new();
+
+    for route in routes {
+        let name = route.name.trim_start_matches("r#");
+
+        if let Some(body) = &route.body {
+            if let (BodyKind::Json | BodyKind::Form, Some(example)) = (body.kind, &body.example) {
+                tests.extend(generate_round_trip_test(
+                    &format!("{name}_request_example"),
+                    &body.owned_type,
+                    example,
+                )?);
             }
-            Some(resolved) => bail!(
-                "resolved to unexpected type {:?}, expected `RequestBody`",
-                resolved
-            ),
-            None => write!(writer, "()")?,
         }
+
+        let kind = route.response.kind;
+
+        if let (true, Some(example)) = (
+            kind == ResponseType::Json || kind.is_stream(),
+            &route.response.example,
+        ) {
+            tests.extend(generate_round_trip_test(
+                &format!("{name}_response_example"),
+                &route.response.type_,
+                example,
+            )?);
+        }
+    }
+
+    if tests.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(quote! {
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            #(#tests)*
+        }
+    }))
+}
+
+fn generate_round_trip_test(
+    name: &str,
+    type_: &str,
+    example: &Value,
+) -> Result<Option<TokenStream>> {
+    let value = match json_tokens(example) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let name = ident(name);
+    let type_ = parse_type(type_)?;
+
+    Ok(Some(quote! {
+        #[test]
+        fn #name() {
+            let example = serde_json::json!(#value);
+            let value: #type_ =
+                serde_json::from_value(example).expect("Example does not match the type");
+            let serialized = serde_json::to_value(&value).expect("Failed to serialize example");
+            let _: #type_ = serde_json::from_value(serialized)
+                .expect("Serialized example cannot be deserialized again");
+        }
+    }))
+}
+
+/// Generates the functions turning streaming responses into streams of items, only including
+/// the parsers for the given formats.
+fn generate_stream_helpers(event_stream: bool, json_lines: bool) -> TokenStream {
+    let json_lines = json_lines.then(|| {
+        quote! {
+            /// Parses newline delimited JSON.
+            pub(super) fn json_lines<T: serde::de::DeserializeOwned + Send + 'static>(
+                response: reqwest::Response,
+            ) -> futures::stream::BoxStream<'static, Result<T>> {
+                use futures::StreamExt;
+
+                lines(response)
+                    .filter_map(|line| async move {
+                        match line {
+                            Ok(line) if line.trim().is_empty() => None,
+                            Ok(line) => Some(parse_item(&line)),
+                            Err(err) => Some(Err(err)),
+                        }
+                    })
+                    .boxed()
+            }
+        }
+    });
+    let event_stream = event_stream.then(|| {
+        quote! {
+            /// Parses Server-Sent Events, returning the `data` of every event.
+            pub(super) fn event_stream<T: serde::de::DeserializeOwned + Send + 'static>(
+                response: reqwest::Response,
+            ) -> futures::stream::BoxStream<'static, Result<T>> {
+                use futures::StreamExt;
+
+                futures::stream::unfold(
+                    (lines(response).fuse(), String::new()),
+                    |(mut lines, mut data)| async move {
+                        loop {
+                            match lines.next().await {
+                                // an empty line dispatches the event
+                                Some(Ok(line)) if line.is_empty() => {
+                                    if !data.is_empty() {
+                                        let item = parse_item(&data);
+                                        data.clear();
+                                        return Some((item, (lines, data)));
+                                    }
+                                }
+                                Some(Ok(line)) => {
+                                    if let Some(value) = line.strip_prefix("data:") {
+                                        if !data.is_empty() {
+                                            data.push('\n');
+                                        }
+
+                                        data.push_str(value.strip_prefix(' ').unwrap_or(value));
+                                    }
+                                }
+                                Some(Err(err)) => return Some((Err(err), (lines, data))),
+                                None if data.is_empty() => return None,
+                                None => {
+                                    let item = parse_item(&data);
+                                    data.clear();
+                                    return Some((item, (lines, data)));
+                                }
+                            }
+                        }
+                    },
+                )
+                .boxed()
+            }
+        }
+    });
+
+    quote! {
+        /// Splits the body of the response into lines, as they arrive.
+        fn lines(response: reqwest::Response) -> futures::stream::BoxStream<'static, Result<String>> {
+            use futures::StreamExt;
+
+            let bytes = response.bytes_stream().boxed();
+
+            futures::stream::unfold(
+                (bytes, Vec::new(), false),
+                |(mut bytes, mut buffer, mut done)| async move {
+                    loop {
+                        if let Some(position) = buffer.iter().position(|&byte| byte == b'\n') {
+                            let mut line: Vec<u8> = buffer.drain(..=position).collect();
+                            line.pop();
+
+                            if line.last() == Some(&b'\r') {
+                                line.pop();
+                            }
+
+                            let line = String::from_utf8(line).context("Response is not UTF-8");
+                            return Some((line, (bytes, buffer, done)));
+                        }
+
+                        if done {
+                            if buffer.is_empty() {
+                                return None;
+                            }
+
+                            let line = String::from_utf8(std::mem::take(&mut buffer))
+                                .context("Response is not UTF-8");
+                            return Some((line, (bytes, buffer, done)));
+                        }
+
+                        match bytes.next().await {
+                            Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                            Some(Err(err)) => {
+                                buffer.clear();
+                                return Some((Err(err.into()), (bytes, buffer, true)));
+                            }
+                            None => done = true,
+                        }
+                    }
+                },
+            )
+            .boxed()
+        }
+
+        /// Parses an item, which may also be a plain string instead of JSON.
+        fn parse_item<T: serde::de::DeserializeOwned>(data: &str) -> Result<T> {
+            serde_json::from_str(data)
+                .or_else(|err| {
+                    serde_json::from_value(serde_json::Value::String(data.to_owned()))
+                        .map_err(|_| err)
+                })
+                .context("Failed to parse item")
+        }
+
+        #json_lines
+        #event_stream
     }
+}
 
-    writeln!(writer, "    let response = builder.send()")?;
-    writeln!(writer, "        .await?")?;
-    write!(writer, "        .error_for_status()?")?;
+/// Converts a JSON value into tokens for the `json!` macro, unless it contains escape sequences
+/// that aren't valid in Rust.
+fn json_tokens(value: &Value) -> Option<TokenStream> {
+    value.to_string().parse().ok()
+}
+
+/// Generates the body of a client function, `streaming` is set for the `_stream` variants.
+fn generate_function_body(route: &Route, streaming: bool) -> Result<TokenStream> {
+    let request = generate_request(route, streaming)?;
 
     // Response
-    write!(
-        writer,
-        "{}",
-        match response_type {
-            Some(response_type) => response_type.generate_response_part(),
-            None => ResponseType::fallback_response_part(),
+    let response = match route.response.kind {
+        ResponseType::Bytes if streaming => quote! {
+            let response = client.send(builder).await?.error_for_status()?;
+            let chunks = futures::StreamExt::map(response.bytes_stream(), |chunk| Ok(chunk?));
+
+            Ok(futures::StreamExt::boxed(chunks))
+        },
+        kind => kind.generate_response_part(),
+    };
+
+    Ok(quote! {
+        #request
+        #response
+    })
+}
+
+/// Generates the statements creating the request `builder` of a client function, with the
+/// parameters and the payload taken from the variables named after them.
+pub(crate) fn generate_request(route: &Route, streaming: bool) -> Result<TokenStream> {
+    let method = ident(route.method);
+    let endpoint = route.endpoint;
+    let arguments = route.path_arguments()?;
+
+    let path = if arguments.is_empty() {
+        quote! { #endpoint }
+    } else {
+        // parameter names aren't necessarily valid identifiers, so use positional arguments
+        let template = Regex::new(r#"\{.*?\}"#)
+            .context("Failed to build regex")?
+            .replace_all(endpoint, "{}")
+            .into_owned();
+        let values = arguments.iter().map(|&arg| {
+            let parameter = route
+                .parameters
+                .iter()
+                .find(|parameter| parameter.location == "path" && parameter.name == arg);
+
+            match parameter {
+                Some(parameter) => ident(&parameter.ident),
+                None => ident(&snake_case(arg)),
+            }
+        });
+
+        quote! { &format!(#template, #(#values),*) }
+    };
+
+    // Query strings, headers and cookies as parameters
+    let mut query = Vec::new();
+    let mut headers = Vec::new();
+    let mut cookies = Vec::new();
+
+    for parameter in &route.parameters {
+        let name = &parameter.name;
+        let ident = ident(&parameter.ident);
+
+        let (statement, statements) = match parameter.location.as_str() {
+            "path" => continue,
+            "query" => {
+                let value = match parameter.query {
+                    QuerySerialization::Serde => quote! { #ident },
+                    QuerySerialization::ToString => quote! { #ident.to_string() },
+                    QuerySerialization::Json => quote! { serde_json::to_string(&#ident)? },
+                };
+
+                (
+                    quote! { builder = builder.query(&[(#name, #value)]); },
+                    &mut query,
+                )
+            }
+            "header" => {
+                let value = parameter_text(parameter, &ident);
+
+                (
+                    quote! { builder = builder.header(#name, #value); },
+                    &mut headers,
+                )
+            }
+            "cookie" => {
+                let value = parameter_text(parameter, &ident);
+
+                (quote! { cookies.push((#name, #value)); }, &mut cookies)
+            }
+            location => bail!(
+                "Parameter `{name}` of `{}` is in unsupported location `{location}`",
+                route.name
+            ),
+        };
+
+        statements.push(if parameter.required {
+            statement
+        } else {
+            quote! {
+                if let Some(#ident) = #ident {
+                    #statement
+                }
+            }
+        });
+    }
+
+    // all cookies are sent in a single `Cookie` header
+    let cookies = (!cookies.is_empty()).then(|| {
+        quote! {
+            let mut cookies: Vec<(&str, String)> = Vec::new();
+            #(#cookies)*
+
+            if !cookies.is_empty() {
+                let cookies: Vec<String> = cookies
+                    .into_iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+
+                builder = builder.header(reqwest::header::COOKIE, cookies.join("; "));
+            }
         }
-    )?;
+    });
+
+    // Request body
+    let body = route.body.as_ref().map(|body| match body.kind {
+        BodyKind::Json => quote! { builder = builder.json(&payload); },
+        BodyKind::Form => quote! { builder = builder.form(&payload); },
+        BodyKind::Binary if streaming => quote! {
+            builder = builder.body(payload);
 
-    Ok(())
+            if let Some(content_length) = content_length {
+                builder = builder.header(reqwest::header::CONTENT_LENGTH, content_length);
+            }
+        },
+        BodyKind::Binary => quote! { builder = builder.body(payload); },
+    });
+
+    let request = match &route.server {
+        Some(server) => quote! { client.request_with_base(#server, Method::#method, #path)? },
+        None => quote! { client.request(Method::#method, #path)? },
+    };
+
+    Ok(quote! {
+        let mut builder = #request;
+        #(#query)*
+        #(#headers)*
+        #cookies
+        #body
+    })
 }
 
-#[derive(Debug)]
-enum ResponseType {
+/// Generates the expression turning a header or cookie parameter into the `String` that is sent.
+fn parameter_text(parameter: &RouteParameter, ident: &Ident) -> TokenStream {
+    match parameter.query {
+        QuerySerialization::Serde if parameter.owned_type == "String" => {
+            quote! { #ident.to_string() }
+        }
+        // other values are sent like they'd be serialized in a query string, without quotes
+        QuerySerialization::Serde => quote! {
+            match serde_json::to_value(&#ident)? {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            }
+        },
+        QuerySerialization::ToString => quote! { #ident.to_string() },
+        QuerySerialization::Json => quote! { serde_json::to_string(&#ident)? },
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResponseType {
     Json,
     Text,
+    Bytes,
+    /// Only the response headers, used for `HEAD` requests
+    Headers,
+    /// Server-Sent Events, with every `data` field containing an item
+    EventStream,
+    /// Newline delimited JSON, with an item on every line
+    JsonLines,
     None,
 }
 
 impl ResponseType {
-    fn generate_response_part(&self) -> &'static str {
+    /// Whether the response is a stream of items of the response type.
+    pub(crate) fn is_stream(&self) -> bool {
+        matches!(self, ResponseType::EventStream | ResponseType::JsonLines)
+    }
+
+    fn generate_response_part(&self) -> TokenStream {
         match self {
-            ResponseType::Json => {
-                // newline, 8 intend, newline, 8 intend, 2x newline, 4 intend
-                "\n        .json()\n        .await?;\n\n    Ok(response)"
-            }
-            ResponseType::Text => {
-                // newline, 8 intend, newline, 8 intend, 2x newline, 4 intend
-                "\n        .text()\n        .await?;\n\n    Ok(response)"
-            }
-            ResponseType::None => {
-                // semicolon, 2x newline, 4 intend
-                ";\n\n    Ok(())"
-            }
+            ResponseType::Json => quote! {
+                let response = client.send(builder).await?.error_for_status()?.json().await?;
+
+                Ok(response)
+            },
+            ResponseType::Text => quote! {
+                let response = client.send(builder).await?.error_for_status()?.text().await?;
+
+                Ok(response)
+            },
+            ResponseType::Bytes => quote! {
+                let response = client.send(builder).await?.error_for_status()?.bytes().await?;
+
+                Ok(response)
+            },
+            ResponseType::Headers => quote! {
+                let response = client.send(builder).await?.error_for_status()?;
+
+                Ok(response.headers().clone())
+            },
+            ResponseType::EventStream => quote! {
+                builder = builder.header(reqwest::header::ACCEPT, "text/event-stream");
+                let response = client.send(builder).await?.error_for_status()?;
+
+                Ok(__private::event_stream(response))
+            },
+            ResponseType::JsonLines => quote! {
+                builder = builder.header(reqwest::header::ACCEPT, "application/x-ndjson");
+                let response = client.send(builder).await?.error_for_status()?;
+
+                Ok(__private::json_lines(response))
+            },
+            ResponseType::None => quote! {
+                let response = client.send(builder).await?.error_for_status()?;
+
+                Ok(())
+            },
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+
+    fn query_serialization(parameter: &str) -> QuerySerialization {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let parameter: Parameter = serde_yaml::from_str(parameter).unwrap();
+
+        analyze_parameter(&parameter, "List", &mut BTreeSet::new(), &mapper)
+            .unwrap()
+            .query
+    }
+
+    /// Analyzes a `POST /items/{id}` operation. The operation is leaked, so the route can borrow
+    /// it for the rest of the test.
+    fn route(operation: &str) -> Route<'static> {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let operation: &'static Operation =
+            Box::leak(Box::new(serde_yaml::from_str(operation).unwrap()));
 
-    fn fallback_response_part() -> &'static str {
-        // newline, 8 intend, newline, 8 intend, 2x newline, 4 intend
-        "\n        .bytes()\n        .await?;\n\n    Ok(response)"
+        Route::analyze(
+            "/items/{id}",
+            "POST",
+            operation,
+            &PathItem::default(),
+            &Components::default(),
+            &mapper,
+        )
+        .unwrap()
+    }
+
+    fn success_status(responses: &str) -> String {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let operation: Operation = serde_yaml::from_str(&format!(
+            "{{ operationId: create, responses: {responses} }}"
+        ))
+        .unwrap();
+
+        Route::analyze(
+            "/items",
+            "POST",
+            &operation,
+            &PathItem::default(),
+            &Components::default(),
+            &mapper,
+        )
+        .unwrap()
+        .status
+    }
+
+    #[test]
+    fn success_status_prefers_200() {
+        assert_eq!(
+            success_status("{ '201': { description: Created }, '200': { description: OK } }"),
+            "200"
+        );
+        assert_eq!(
+            success_status("{ '204': { description: Empty }, '201': { description: Created }, '400': { description: Bad } }"),
+            "201"
+        );
+        assert_eq!(success_status("{ '404': { description: Missing } }"), "200");
+    }
+
+    #[test]
+    fn header_and_cookie_parameters_are_sent() {
+        let route = route(
+            r#"
+operationId: list
+parameters:
+  - { name: X-Trace, in: header, required: true, schema: { type: string } }
+  - { name: X-Limit, in: header, schema: { type: integer } }
+  - { name: session, in: cookie, required: true, schema: { type: string } }
+  - { name: theme, in: cookie, schema: { type: string } }
+responses: {}
+"#,
+        );
+
+        let request = generate_request(&route, false)
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+
+        assert!(request.contains(r#"builder=builder.header("X-Trace",x_trace.to_string());"#));
+        assert!(request.contains(
+            r#"ifletSome(x_limit)=x_limit{builder=builder.header("X-Limit",matchserde_json::to_value(&x_limit)?"#
+        ));
+        assert!(request.contains(r#"cookies.push(("session",session.to_string()));"#));
+        assert!(request
+            .contains(r#"ifletSome(theme)=theme{cookies.push(("theme",theme.to_string()));}"#));
+        assert!(request
+            .contains(r#"builder=builder.header(reqwest::header::COOKIE,cookies.join(";"));"#));
+    }
+
+    #[test]
+    fn parameters_in_unknown_locations_are_rejected() {
+        let mut route = route(
+            "{ operationId: list, parameters: [{ name: a, in: query, schema: { type: string } }], responses: {} }",
+        );
+        route.parameters[0].location = "body".to_owned();
+
+        assert_eq!(
+            generate_request(&route, false).unwrap_err().to_string(),
+            "Parameter `a` of `list` is in unsupported location `body`"
+        );
+    }
+
+    #[test]
+    fn docs_are_generated_from_the_operation() {
+        let route = route(
+            r##"
+operationId: update
+summary: Updates an item
+description: |
+  Only the given fields are changed.
+  Quotes like "# don't end the docs.
+deprecated: true
+externalDocs:
+  url: https://example.com/items
+parameters:
+  - name: id
+    in: path
+    required: true
+    description: ID of the item
+    schema: { type: string }
+  - name: notify
+    in: query
+    deprecated: true
+    schema: { type: boolean }
+requestBody:
+  description: The fields to change
+  content:
+    application/json:
+      schema: { type: object, properties: { title: { type: string } } }
+responses:
+  "200": { description: The updated item }
+  "404":
+    description: |
+      The item doesn't exist
+      or was deleted
+  default: { description: "" }
+"##,
+        );
+
+        let docs: Vec<String> = generate_docs(&route, None)
+            .into_iter()
+            .filter_map(|token| match token {
+                proc_macro2::TokenTree::Group(group) => Some(group.stream().to_string()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            docs,
+            [
+                r#"doc = " Updates an item""#,
+                r#"doc = """#,
+                r#"doc = " Only the given fields are changed.""#,
+                r##"doc = " Quotes like \"# don't end the docs.""##,
+                r#"doc = """#,
+                r#"doc = " See also: [External documentation](https://example.com/items)""#,
+                r#"doc = """#,
+                r#"doc = " # Arguments""#,
+                r#"doc = """#,
+                r#"doc = " * `id` - ID of the item""#,
+                r#"doc = " * `notify` - **Deprecated.**""#,
+                r#"doc = " * `payload` - The fields to change""#,
+                r#"doc = """#,
+                r#"doc = " # Errors""#,
+                r#"doc = """#,
+                r#"doc = " Documented error responses:""#,
+                r#"doc = """#,
+                r#"doc = " * `404` - The item doesn't exist""#,
+                r#"doc = "   or was deleted""#,
+                r#"doc = " * Other status codes""#,
+                "deprecated",
+            ]
+        );
+    }
+
+    #[test]
+    fn examples_of_the_parameter_take_precedence_over_the_schema() {
+        let route = route(
+            r#"
+operationId: update
+parameters:
+  - { name: id, in: path, required: true, example: a, schema: { type: string, example: b } }
+  - name: page
+    in: query
+    examples: { first: { value: 1 } }
+    schema: { type: integer, example: 2 }
+  - { name: limit, in: query, schema: { type: integer, example: 3 } }
+responses: {}
+"#,
+        );
+        let examples: Vec<_> = route
+            .parameters
+            .iter()
+            .map(|parameter| parameter.example.clone())
+            .collect();
+
+        assert_eq!(examples, [Some(json!("a")), Some(json!(1)), Some(json!(3))]);
+    }
+
+    #[test]
+    fn examples_are_only_generated_when_required_values_have_examples() {
+        let operation = |body_example: &str| {
+            format!(
+                r#"
+operationId: update
+parameters:
+  - {{ name: id, in: path, required: true, example: item-1, schema: {{ type: string }} }}
+  - {{ name: force, in: query, schema: {{ type: boolean }} }}
+requestBody:
+  required: true
+  content:
+    application/json:
+      schema: {{ type: object, properties: {{ title: {{ type: string }} }} }}
+      {body_example}
+responses:
+  "200":
+    description: The updated item
+    content:
+      application/json: {{ schema: {{ $ref: '#/components/schemas/Item' }} }}
+"#
+            )
+        };
+
+        assert!(generate_example(&route(&operation("")), "api")
+            .unwrap()
+            .is_none());
+
+        let example = generate_example(&route(&operation("example: { title: New }")), "api")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            example,
+            r#"# Example
+
+```no_run
+# async fn example() -> anyhow::Result<()> {
+let client = api::clients::ApiClient::builder("https://api.example.com".parse()?)
+    .build()?;
+let id: String = serde_json::from_value(serde_json::json!("item-1"))?;
+let force: Option<bool> = None;
+let payload: api::types::UpdateRequest = serde_json::from_value(
+    serde_json::json!({ "title" : "New" }),
+)?;
+let response = api::update(&client, &id, force, payload).await?;
+# Ok(())
+# }
+```"#
+        );
+    }
+
+    #[test]
+    fn streaming_responses_are_parsed_into_items() {
+        let events = route(
+            r#"
+operationId: watch
+responses:
+  "200":
+    description: Events
+    content:
+      text/event-stream: { schema: { $ref: '#/components/schemas/Item' } }
+"#,
+        );
+        assert_eq!(events.response.kind, ResponseType::EventStream);
+        assert_eq!(events.response.type_, "models::Item");
+
+        let function = generate_route(&events, "api")
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+        assert!(
+            function.contains("->Result<futures::stream::BoxStream<'static,Result<models::Item>>>")
+        );
+        assert!(function
+            .contains(r#"builder=builder.header(reqwest::header::ACCEPT,"text/event-stream");"#));
+        assert!(function.contains("Ok(__private::event_stream(response))"));
+
+        // only the parsers of the used formats are generated
+        let private = generate_private_module(&[events]).unwrap().to_string();
+        assert!(private.contains("fn event_stream"));
+        assert!(!private.contains("fn json_lines"));
+
+        let lines = route(
+            r#"
+operationId: lines
+responses:
+  "200":
+    description: Items
+    content:
+      application/x-ndjson: { schema: { type: object, properties: { id: { type: string } } } }
+"#,
+        );
+        assert_eq!(lines.response.kind, ResponseType::JsonLines);
+        assert_eq!(lines.response.type_, "types::LinesResponseItem");
+
+        let private = generate_private_module(&[lines]).unwrap().to_string();
+        assert!(private.contains("fn json_lines"));
+        assert!(!private.contains("fn event_stream"));
+    }
+
+    #[test]
+    fn binary_bodies_get_streaming_variants() {
+        let upload = route(
+            r#"
+operationId: upload
+requestBody:
+  content:
+    application/octet-stream: { schema: { type: string, format: binary } }
+responses:
+  "200":
+    description: The converted file
+    content:
+      application/octet-stream: { schema: { type: string, format: binary } }
+"#,
+        );
+        assert_eq!(upload.body.as_ref().unwrap().kind, BodyKind::Binary);
+        assert_eq!(upload.response.kind, ResponseType::Bytes);
+
+        let function = generate_streaming_route(&upload)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+        assert!(function.contains(
+            "pubasyncfnupload_stream(client:&ApiClient,payload:reqwest::Body,\
+            content_length:Option<u64>)->Result<futures::stream::BoxStream<'static,\
+            Result<bytes::Bytes>>>"
+        ));
+        assert!(function.contains(
+            "builder=builder.body(payload);ifletSome(content_length)=content_length{\
+            builder=builder.header(reqwest::header::CONTENT_LENGTH,content_length);}"
+        ));
+        assert!(function.contains("response.bytes_stream()"));
+
+        let json = route(
+            r#"
+operationId: update
+requestBody:
+  content:
+    application/json: { schema: { type: object, properties: { title: { type: string } } } }
+responses: {}
+"#,
+        );
+        assert!(generate_streaming_route(&json).unwrap().is_none());
+    }
+
+    #[test]
+    fn documented_response_headers_are_parsed() {
+        let create = route(
+            r#"
+operationId: create
+responses:
+  "201":
+    description: Created
+    headers:
+      Location: { required: true, schema: { type: string, format: uri } }
+      X-Rate-Limit: { description: Remaining requests, schema: { type: integer } }
+"#,
+        );
+        let function = generate_response_route(&create)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+
+        assert!(function.contains(
+            r#"pubstructCreateResponseHeaders{publocation:url::Url,#[doc="Remainingrequests"]pubx_rate_limit:Option<i32>,}"#
+        ));
+        assert!(function.contains("->Result<ApiResponse<(),CreateResponseHeaders>>"));
+        assert!(function.contains(
+            r#"location:__private::header_value(response.headers(),"Location")?.context("Missing`Location`header")?,"#
+        ));
+        assert!(function.contains(
+            r#"x_rate_limit:__private::header_value(response.headers(),"X-Rate-Limit")?,"#
+        ));
+
+        let get = route(
+            r#"
+operationId: get
+responses:
+  "200":
+    description: The item
+    content:
+      application/json: { schema: { $ref: '#/components/schemas/Item' } }
+"#,
+        );
+        let function = generate_response_route(&get)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+
+        assert!(function.contains("->Result<ApiResponse<models::Item,()>>"));
+        assert!(!function.contains("pubstruct"));
+    }
+
+    #[test]
+    fn options_variants_forward_to_the_operation() {
+        let route = route(
+            r#"
+operationId: update
+deprecated: true
+parameters:
+  - { name: id, in: path, required: true, schema: { type: string } }
+  - { name: update, in: query, schema: { type: boolean } }
+requestBody:
+  content:
+    application/json: { schema: { type: object, properties: { title: { type: string } } } }
+responses: {}
+"#,
+        );
+        let function = generate_options_route(&route)
+            .unwrap()
+            .to_string()
+            .replace(char::is_whitespace, "");
+
+        assert!(function.contains(
+            "#[deprecated]#[allow(deprecated)]pubasyncfnupdate_with_options(client:&ApiClient,\
+            id:&str,update:Option<bool>,payload:types::UpdateRequest,options:RequestOptions,)\
+            ->Result<()>{self::update(&client.with_options(options),id,update,payload).await}"
+        ));
+    }
+
+    #[test]
+    fn servers_of_the_operation_take_precedence_over_the_path() {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let item: PathItem = serde_yaml::from_str(
+            r#"
+servers: [{ url: "https://files.example.com/{version}", variables: { version: { default: v1 } } }]
+head: { operationId: check, responses: {} }
+trace: { operationId: trace, servers: [{ url: /debug }], responses: {} }
+"#,
+        )
+        .unwrap();
+        let server = |operation| {
+            Route::analyze(
+                "/files",
+                "GET",
+                operation,
+                &item,
+                &Components::default(),
+                &mapper,
+            )
+            .unwrap()
+            .server
+        };
+
+        assert_eq!(
+            server(item.head.as_ref().unwrap()).as_deref(),
+            Some("https://files.example.com/v1")
+        );
+        assert_eq!(
+            server(item.trace.as_ref().unwrap()).as_deref(),
+            Some("/debug")
+        );
+        assert_eq!(route("{ responses: {} }").server, None);
+    }
+
+    #[test]
+    fn only_object_query_parameters_are_json_encoded() {
+        assert_eq!(
+            query_serialization(
+                "{ name: sort, in: query, schema: { $ref: '#/components/schemas/Sort' } }"
+            ),
+            QuerySerialization::Serde
+        );
+        assert_eq!(
+            query_serialization("{ name: cursor, in: query, schema: { type: string } }"),
+            QuerySerialization::Serde
+        );
+        assert_eq!(
+            query_serialization(
+                "{ name: labels, in: query, schema: { type: object, additionalProperties: { type: string } } }"
+            ),
+            QuerySerialization::Json
+        );
     }
 }
@@ -0,0 +1,694 @@
+use crate::codegen::{doc, format_file, ident, parse_type, pascal_case, unique_name};
+use crate::config::QuerySerialization;
+use crate::routes::{
+    analyze_response, generate_docs, generate_models_module, operations, BodyKind, ResponseType,
+    Route, RouteParameter,
+};
+use crate::types::{resolve, ResolveTarget, ResolvedReference, TypeMapper};
+use anyhow::{anyhow, bail, Result};
+use okapi::openapi3::{Components, PathItem};
+use okapi::Map;
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::quote;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(crate) fn generate_server(
+    paths: &Map<String, PathItem>,
+    components: &Components,
+    mapper: &TypeMapper,
+) -> Result<String> {
+    let models = generate_models_module(&mapper.config.models)?;
+
+    let mut routes = Vec::new();
+
+    for (endpoint, item) in paths {
+        for (method, operation) in operations(item) {
+            routes.push(Route::analyze(
+                endpoint, method, operation, item, components, mapper,
+            )?);
+        }
+    }
+
+    let errors = routes
+        .iter()
+        .map(|route| analyze_errors(route, components, mapper))
+        .collect::<Result<Vec<_>>>()?;
+
+    let server_trait = generate_trait(&routes, &errors)?;
+    let error_enums = routes
+        .iter()
+        .zip(&errors)
+        .map(|(route, errors)| generate_error_enum(route, errors))
+        .collect::<Result<Vec<_>>>()?;
+    let handlers = routes
+        .iter()
+        .map(generate_handler)
+        .collect::<Result<Vec<_>>>()?;
+    let router = generate_router(&routes);
+    let types_module = mapper.has_types().then(|| quote! { pub mod types; });
+    let helpers = generate_helpers();
+
+    format_file(quote! {
+        #![forbid(unsafe_code)]
+        #![allow(dead_code)]
+        #![allow(unused_variables)]
+        #![allow(unused_imports)]
+
+        use async_trait::async_trait;
+        use axum::body::StreamBody;
+        use axum::extract::{Path, Query, State};
+        use axum::http::{header, HeaderMap, StatusCode};
+        use axum::response::sse::{Event, KeepAlive, Sse};
+        use axum::response::{IntoResponse, Response};
+        use axum::{Form, Json, Router};
+        use serde::Deserialize;
+        use std::sync::Arc;
+
+        #types_module
+
+        #models
+        #server_trait
+        #(#error_enums)*
+        #(#handlers)*
+        #router
+        #helpers
+    })
+}
+
+/// A documented error response of an operation.
+struct ErrorResponse {
+    variant: String,
+    /// The status code, `None` for ranges (`4XX`) and `default`, where the variant holds it
+    status: Option<TokenStream>,
+    description: String,
+    kind: ResponseType,
+    type_: String,
+}
+
+/// Names of the `StatusCode` constants of the status codes that have one.
+const STATUS_CODES: &[(&str, &str)] = &[
+    ("200", "OK"),
+    ("201", "CREATED"),
+    ("202", "ACCEPTED"),
+    ("203", "NON_AUTHORITATIVE_INFORMATION"),
+    ("204", "NO_CONTENT"),
+    ("205", "RESET_CONTENT"),
+    ("206", "PARTIAL_CONTENT"),
+    ("300", "MULTIPLE_CHOICES"),
+    ("301", "MOVED_PERMANENTLY"),
+    ("302", "FOUND"),
+    ("303", "SEE_OTHER"),
+    ("304", "NOT_MODIFIED"),
+    ("307", "TEMPORARY_REDIRECT"),
+    ("308", "PERMANENT_REDIRECT"),
+    ("400", "BAD_REQUEST"),
+    ("401", "UNAUTHORIZED"),
+    ("402", "PAYMENT_REQUIRED"),
+    ("403", "FORBIDDEN"),
+    ("404", "NOT_FOUND"),
+    ("405", "METHOD_NOT_ALLOWED"),
+    ("406", "NOT_ACCEPTABLE"),
+    ("408", "REQUEST_TIMEOUT"),
+    ("409", "CONFLICT"),
+    ("410", "GONE"),
+    ("411", "LENGTH_REQUIRED"),
+    ("412", "PRECONDITION_FAILED"),
+    ("413", "PAYLOAD_TOO_LARGE"),
+    ("415", "UNSUPPORTED_MEDIA_TYPE"),
+    ("416", "RANGE_NOT_SATISFIABLE"),
+    ("422", "UNPROCESSABLE_ENTITY"),
+    ("423", "LOCKED"),
+    ("428", "PRECONDITION_REQUIRED"),
+    ("429", "TOO_MANY_REQUESTS"),
+    ("500", "INTERNAL_SERVER_ERROR"),
+    ("501", "NOT_IMPLEMENTED"),
+    ("502", "BAD_GATEWAY"),
+    ("503", "SERVICE_UNAVAILABLE"),
+    ("504", "GATEWAY_TIMEOUT"),
+];
+
+/// Returns the expression for a documented status code.
+fn status_code(status: &str) -> Result<TokenStream> {
+    if let Some((_, name)) = STATUS_CODES.iter().find(|(code, _)| *code == status) {
+        let name = ident(name);
+        return Ok(quote! { StatusCode::#name });
+    }
+
+    let code: u16 = status
+        .parse()
+        .ok()
+        .filter(|code| (100..1000).contains(code))
+        .ok_or_else(|| anyhow!("Invalid status code `{status}`"))?;
+
+    let code = Literal::u16_unsuffixed(code);
+
+    Ok(quote! { StatusCode::from_u16(#code).expect("Invalid status code") })
+}
+
+/// Analyzes the documented error responses of the route, which are all non-2xx responses and
+/// the `default` one.
+fn analyze_errors(
+    route: &Route,
+    components: &Components,
+    mapper: &TypeMapper,
+) -> Result<Vec<ErrorResponse>> {
+    let prefix = pascal_case(&route.name);
+    let responses = &route.operation.responses;
+    // `Other` holds the errors of the server itself
+    let mut taken = BTreeSet::from(["Other".to_owned()]);
+    let mut errors = Vec::new();
+
+    for (status, response) in responses
+        .responses
+        .iter()
+        .filter(|(status, _)| !status.starts_with('2'))
+        .map(|(status, response)| (status.as_str(), response))
+        .chain(
+            responses
+                .default
+                .iter()
+                .map(|response| ("default", response)),
+        )
+    {
+        let response = Some(response);
+        let response = match resolve(ResolveTarget::Response(&response), components)? {
+            Some(ResolvedReference::Responses(response)) => response,
+            _ => bail!(
+                "Failed to resolve the `{status}` response of `{}`",
+                route.name
+            ),
+        };
+
+        let (variant, status_code) = match status {
+            "default" => ("Default".to_owned(), None),
+            "4XX" => ("ClientError".to_owned(), None),
+            "5XX" => ("ServerError".to_owned(), None),
+            _ if status.ends_with("XX") => (format!("Status{}", status.to_lowercase()), None),
+            _ => {
+                let variant = match STATUS_CODES.iter().find(|(code, _)| *code == status) {
+                    Some((_, name)) => pascal_case(name),
+                    None => format!("Status{status}"),
+                };
+
+                (variant, Some(status_code(status)?))
+            }
+        };
+        let variant = unique_name(variant, &mut taken);
+
+        let mut body = analyze_response(&response, &format!("{prefix}{variant}"), mapper)?;
+
+        if body.kind.is_stream() {
+            eprintln!(
+                "warn: the body of the `{status}` response of `{}` is ignored",
+                route.name
+            );
+            body.kind = ResponseType::None;
+        }
+
+        errors.push(ErrorResponse {
+            variant,
+            status: status_code,
+            description: response.description.clone(),
+            kind: body.kind,
+            type_: body.type_,
+        });
+    }
+
+    Ok(errors)
+}
+
+/// Generates the enum of the documented error responses of a route, which also holds the errors
+/// of the server itself.
+fn generate_error_enum(route: &Route, errors: &[ErrorResponse]) -> Result<Option<TokenStream>> {
+    if errors.is_empty() {
+        return Ok(None);
+    }
+
+    let name = error_enum_name(route);
+    let docs = doc(&format!(
+        "The documented error responses of [`ApiServer::{}`].",
+        route.name.trim_start_matches("r#")
+    ));
+
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+
+    for error in errors {
+        let variant = ident(&error.variant);
+        let docs = doc(&error.description);
+
+        let body = match error.kind {
+            ResponseType::None | ResponseType::Headers => None,
+            _ => Some(parse_type(&error.type_)?),
+        };
+        let response = match error.kind {
+            ResponseType::Json => quote! { Json(body) },
+            _ => quote! { body },
+        };
+
+        match (&error.status, body) {
+            (Some(status), Some(body)) => {
+                variants.push(quote! { #docs #variant(#body) });
+                arms.push(quote! { Self::#variant(body) => (#status, #response).into_response() });
+            }
+            (Some(status), None) => {
+                variants.push(quote! { #docs #variant });
+                arms.push(quote! { Self::#variant => #status.into_response() });
+            }
+            (None, Some(body)) => {
+                variants.push(quote! { #docs #variant(StatusCode, #body) });
+                arms.push(
+                    quote! { Self::#variant(status, body) => (status, #response).into_response() },
+                );
+            }
+            (None, None) => {
+                variants.push(quote! { #docs #variant(StatusCode) });
+                arms.push(quote! { Self::#variant(status) => status.into_response() });
+            }
+        }
+    }
+
+    Ok(Some(quote! {
+        #docs
+        #[derive(Debug)]
+        pub enum #name<E> {
+            #(#variants,)*
+            /// Any other error, which is turned into a response by its own `IntoResponse`
+            /// implementation
+            Other(E),
+        }
+
+        impl<E> From<E> for #name<E> {
+            fn from(err: E) -> Self {
+                Self::Other(err)
+            }
+        }
+
+        impl<E: IntoResponse> IntoResponse for #name<E> {
+            fn into_response(self) -> Response {
+                match self {
+                    #(#arms,)*
+                    Self::Other(err) => err.into_response(),
+                }
+            }
+        }
+    }))
+}
+
+fn error_enum_name(route: &Route) -> Ident {
+    ident(&format!("{}Error", pascal_case(&route.name)))
+}
+
+fn generate_trait(routes: &[Route], errors: &[Vec<ErrorResponse>]) -> Result<TokenStream> {
+    let mut methods = Vec::new();
+
+    for (route, errors) in routes.iter().zip(errors) {
+        let docs = generate_docs(route, None);
+
+        let name = ident(&route.name);
+
+        let parameters = route
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let ident = ident(&parameter.ident);
+                let type_ = owned_parameter_type(parameter)?;
+
+                Ok(quote! { #ident: #type_ })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let payload = match &route.body {
+            Some(body) => {
+                let type_ = parse_type(&body.owned_type)?;
+                Some(quote! { payload: #type_ })
+            }
+            None => None,
+        };
+
+        let response = match route.response.kind {
+            ResponseType::Headers => quote! { HeaderMap },
+            ResponseType::EventStream | ResponseType::JsonLines => {
+                let type_ = parse_type(&route.response.type_)?;
+                quote! { futures::stream::BoxStream<'static, #type_> }
+            }
+            _ => {
+                let type_ = parse_type(&route.response.type_)?;
+                quote! { #type_ }
+            }
+        };
+
+        let error = if errors.is_empty() {
+            quote! { Self::Error }
+        } else {
+            let error = error_enum_name(route);
+            quote! { #error<Self::Error> }
+        };
+
+        methods.push(quote! {
+            #docs
+            async fn #name(
+                &self,
+                #(#parameters,)*
+                #payload
+            ) -> Result<#response, #error>;
+        });
+    }
+
+    Ok(quote! {
+        /// Implement this trait to serve the API using [`router`].
+        #[async_trait]
+        pub trait ApiServer: Send + Sync + 'static {
+            /// Error returned by the handlers, which is turned into the response for the client.
+            /// Operations with documented error responses return those as part of an enum
+            /// holding this error as well.
+            type Error: IntoResponse + Send;
+
+            #(#methods)*
+        }
+    })
+}
+
+fn generate_handler(route: &Route) -> Result<TokenStream> {
+    let struct_prefix = pascal_case(&route.name);
+    let name = ident(&route.name);
+
+    let path_parameters = parameters_in(route, "path");
+    let query_parameters = parameters_in(route, "query");
+
+    let mut structs = Vec::new();
+    let mut extractors = vec![quote! { State(server): State<Arc<S>> }];
+
+    if !path_parameters.is_empty() {
+        let path = ident(&format!("{struct_prefix}Path"));
+
+        structs.push(generate_extractor_struct(&path, &path_parameters)?);
+        extractors.push(quote! { Path(path): Path<#path> });
+    }
+
+    if !query_parameters.is_empty() {
+        let query = ident(&format!("{struct_prefix}Query"));
+
+        structs.push(generate_extractor_struct(&query, &query_parameters)?);
+        extractors.push(quote! { Query(query): Query<#query> });
+    }
+
+    extractors.push(quote! { headers: HeaderMap });
+
+    // the body extractor needs to come last
+    if let Some(body) = &route.body {
+        let type_ = parse_type(&body.owned_type)?;
+
+        extractors.push(match body.kind {
+            BodyKind::Json => quote! { Json(payload): Json<#type_> },
+            BodyKind::Form => quote! { Form(payload): Form<#type_> },
+            BodyKind::Binary => quote! { payload: bytes::Bytes },
+        });
+    }
+
+    let mut header_values = Vec::new();
+    let mut arguments = Vec::new();
+
+    for parameter in &route.parameters {
+        let ident = ident(&parameter.ident);
+
+        match parameter.location.as_str() {
+            "path" => arguments.push(quote! { path.#ident }),
+            "query" => arguments.push(quote! { query.#ident }),
+            // cookies are sent in the `Cookie` header, so both are taken from the headers
+            location @ ("header" | "cookie") => {
+                let name = &parameter.name;
+                let function = match (location, parameter.required) {
+                    ("header", true) => quote! { required_header },
+                    ("header", false) => quote! { optional_header },
+                    (_, true) => quote! { required_cookie },
+                    (_, false) => quote! { optional_cookie },
+                };
+
+                header_values.push(quote! {
+                    let #ident = match #function(&headers, #name) {
+                        Ok(value) => value,
+                        Err(response) => return response,
+                    };
+                });
+
+                arguments.push(quote! { #ident });
+            }
+            location => bail!(
+                "Parameter `{}` of `{}` is in unsupported location `{location}`",
+                parameter.name,
+                route.name
+            ),
+        }
+    }
+
+    if let Some(body) = &route.body {
+        if body.kind == BodyKind::Binary && body.owned_type != "bytes::Bytes" {
+            arguments.push(quote! { payload.to_vec() });
+        } else {
+            arguments.push(quote! { payload });
+        }
+    }
+
+    // ranges like `2XX` are answered with the first status code of the range
+    let status = status_code(&route.status.replace("XX", "00"))?;
+    let response = match route.response.kind {
+        ResponseType::Json => quote! { Ok(response) => (#status, Json(response)).into_response() },
+        ResponseType::Text | ResponseType::Bytes => {
+            quote! { Ok(response) => (#status, response).into_response() }
+        }
+        ResponseType::Headers => {
+            quote! { Ok(headers) => (#status, headers).into_response() }
+        }
+        ResponseType::EventStream => quote! {
+            Ok(stream) => {
+                let events =
+                    futures::StreamExt::map(stream, |item| Event::default().json_data(item));
+                let events = Sse::new(events).keep_alive(KeepAlive::default());
+
+                (#status, events).into_response()
+            }
+        },
+        ResponseType::JsonLines => quote! {
+            Ok(stream) => {
+                let lines = futures::StreamExt::map(stream, |item| {
+                    serde_json::to_vec(&item).map(|mut line| {
+                        line.push(b'\n');
+                        line
+                    })
+                });
+
+                (
+                    #status,
+                    [(header::CONTENT_TYPE, "application/x-ndjson")],
+                    StreamBody::new(lines),
+                )
+                    .into_response()
+            }
+        },
+        ResponseType::None => quote! { Ok(()) => #status.into_response() },
+    };
+    // calls the deprecated method of the trait
+    let deprecated = route
+        .operation
+        .deprecated
+        .then(|| quote! { #[allow(deprecated)] });
+
+    Ok(quote! {
+        #(#structs)*
+
+        #deprecated
+        async fn #name<S: ApiServer>(#(#extractors),*) -> Response {
+            #(#header_values)*
+
+            match server.#name(#(#arguments),*).await {
+                #response,
+                Err(err) => err.into_response(),
+            }
+        }
+    })
+}
+
+fn generate_extractor_struct(name: &Ident, parameters: &[&RouteParameter]) -> Result<TokenStream> {
+    let mut fields = Vec::new();
+
+    for parameter in parameters {
+        let rename = if parameter.name != parameter.ident {
+            let name = &parameter.name;
+            Some(quote! { #[serde(rename = #name)] })
+        } else {
+            None
+        };
+
+        // the client sends these as JSON encoded strings
+        let deserialize_with = if parameter.query == QuerySerialization::Json {
+            if parameter.required {
+                Some(quote! { #[serde(deserialize_with = "json_string")] })
+            } else {
+                Some(quote! { #[serde(default, deserialize_with = "optional_json_string")] })
+            }
+        } else {
+            None
+        };
+
+        let ident = ident(&parameter.ident);
+        let type_ = owned_parameter_type(parameter)?;
+
+        fields.push(quote! {
+            #rename
+            #deserialize_with
+            #ident: #type_
+        });
+    }
+
+    Ok(quote! {
+        #[derive(Deserialize)]
+        struct #name {
+            #(#fields,)*
+        }
+    })
+}
+
+fn generate_router(routes: &[Route]) -> TokenStream {
+    // group by endpoint, so every path only gets registered once
+    let mut endpoints: BTreeMap<&str, Vec<&Route>> = BTreeMap::new();
+
+    for route in routes {
+        endpoints.entry(route.endpoint).or_default().push(route);
+    }
+
+    let routes = endpoints.into_iter().map(|(endpoint, routes)| {
+        let path = axum_path(endpoint);
+        let handlers = routes.iter().map(|route| {
+            let method = ident(&route.method.to_lowercase());
+            let name = ident(&route.name);
+
+            quote! { #method(#name::<S>) }
+        });
+
+        // the first method is a function, which is called by its path so operations can have
+        // the same name
+        quote! { .route(#path, axum::routing::#(#handlers).*) }
+    });
+
+    quote! {
+        /// Creates a [`Router`] that dispatches all operations to the given server.
+        pub fn router<S: ApiServer>(server: Arc<S>) -> Router {
+            Router::new()
+                #(#routes)*
+                .with_state(server)
+        }
+    }
+}
+
+/// Names of the functions generated next to the handlers, which operations can't be named like.
+pub(crate) const RESERVED_SERVER_NAMES: &[&str] = &[
+    "router",
+    "optional_header",
+    "required_header",
+    "optional_cookie",
+    "required_cookie",
+    "json_string",
+    "optional_json_string",
+];
+
+fn generate_helpers() -> TokenStream {
+    quote! {
+        fn optional_header<T: std::str::FromStr>(
+            headers: &HeaderMap,
+            name: &str,
+        ) -> Result<Option<T>, Response> {
+            match headers.get(name) {
+                Some(value) => value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .map(Some)
+                    .ok_or_else(|| {
+                        (StatusCode::BAD_REQUEST, format!("Invalid header `{name}`")).into_response()
+                    }),
+                None => Ok(None),
+            }
+        }
+
+        fn required_header<T: std::str::FromStr>(
+            headers: &HeaderMap,
+            name: &str,
+        ) -> Result<T, Response> {
+            optional_header(headers, name)?.ok_or_else(|| {
+                (StatusCode::BAD_REQUEST, format!("Missing header `{name}`")).into_response()
+            })
+        }
+
+        fn optional_cookie<T: std::str::FromStr>(
+            headers: &HeaderMap,
+            name: &str,
+        ) -> Result<Option<T>, Response> {
+            let value = headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .find_map(|cookie| match cookie.trim().split_once('=') {
+                    Some((key, value)) if key == name => Some(value),
+                    _ => None,
+                });
+
+            match value {
+                Some(value) => value.parse().map(Some).map_err(|_| {
+                    (StatusCode::BAD_REQUEST, format!("Invalid cookie `{name}`")).into_response()
+                }),
+                None => Ok(None),
+            }
+        }
+
+        fn required_cookie<T: std::str::FromStr>(
+            headers: &HeaderMap,
+            name: &str,
+        ) -> Result<T, Response> {
+            optional_cookie(headers, name)?.ok_or_else(|| {
+                (StatusCode::BAD_REQUEST, format!("Missing cookie `{name}`")).into_response()
+            })
+        }
+
+        fn json_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+            T: serde::de::DeserializeOwned,
+        {
+            let string = String::deserialize(deserializer)?;
+            serde_json::from_str(&string).map_err(serde::de::Error::custom)
+        }
+
+        fn optional_json_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+            T: serde::de::DeserializeOwned,
+        {
+            json_string(deserializer).map(Some)
+        }
+    }
+}
+
+fn parameters_in<'a>(route: &'a Route, location: &str) -> Vec<&'a RouteParameter> {
+    route
+        .parameters
+        .iter()
+        .filter(|parameter| parameter.location == location)
+        .collect()
+}
+
+fn owned_parameter_type(parameter: &RouteParameter) -> Result<TokenStream> {
+    let type_ = parse_type(&parameter.owned_type)?;
+
+    Ok(if parameter.required {
+        quote! { #type_ }
+    } else {
+        quote! { Option<#type_> }
+    })
+}
+
+/// Converts an OpenAPI path template (`/notebooks/{id}`) into the syntax used by axum (`/notebooks/:id`)
+fn axum_path(endpoint: &str) -> String {
+    endpoint.replace('{', ":").replace('}', "")
+}
@@ -1,69 +1,518 @@
+use crate::codegen::{doc, format_file, ident, parse_type, pascal_case, snake_case, unique_name};
+use crate::config::{Config, TypeMapping};
 use anyhow::{anyhow, bail, Result};
-use convert_case::{Case, Casing};
 use okapi::openapi3::{Components, Parameter, RefOr, RequestBody, Response};
-use schemars::schema::{InstanceType, SchemaObject, SingleOrVec};
+use proc_macro2::TokenStream;
+use quote::quote;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
 
-pub(crate) fn map_type<'a>(
-    format: Option<&str>,
-    instance_type: Option<&'a SingleOrVec<InstanceType>>,
-    reference: Option<&'a str>,
-    as_ref: bool,
-) -> Result<Cow<'a, str>> {
-    Ok(match format {
-        Some("base64uuid") => "base64uuid::Base64Uuid".into(),
-        Some("name") => {
-            if as_ref {
-                "&fiberplane_models::names::Name".into()
+/// Returns the configured mapping for the schema, looked up by its `x-rust-type` extension and
+/// then by its `format`.
+pub(crate) fn find_mapping(schema: &SchemaObject, config: &Config) -> Option<TypeMapping> {
+    rust_type_extension(schema)
+        .and_then(|rust_type| config.type_mapping(rust_type))
+        .or_else(|| {
+            schema
+                .format
+                .as_deref()
+                .and_then(|format| config.type_mapping(format))
+        })
+}
+
+fn rust_type_extension(schema: &SchemaObject) -> Option<&str> {
+    schema
+        .extensions
+        .get("x-rust-type")
+        .and_then(|value| value.as_str())
+}
+
+/// Maps schemas to Rust types, collecting the structs generated for inline object schemas on
+/// the way. Shared between all operations of a generated crate.
+pub(crate) struct TypeMapper<'a> {
+    pub(crate) config: &'a Config,
+    /// Generated structs by name
+    structs: RefCell<BTreeMap<String, TokenStream>>,
+    /// Whether the `Base64` type is used
+    base64: Cell<bool>,
+    /// Whether the `Nullable` type is used
+    nullable: Cell<bool>,
+}
+
+impl<'a> TypeMapper<'a> {
+    pub(crate) fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            structs: RefCell::new(BTreeMap::new()),
+            base64: Cell::new(false),
+            nullable: Cell::new(false),
+        }
+    }
+
+    /// Maps the schema to a Rust type. `name` is used for the struct if the schema is an inline
+    /// object, and as prefix for the structs of nested objects.
+    pub(crate) fn map_type<'s>(
+        &self,
+        schema: &'s SchemaObject,
+        name: &str,
+        as_ref: bool,
+    ) -> Result<Cow<'s, str>> {
+        if let Some(mapping) = find_mapping(schema, self.config) {
+            return Ok(if as_ref && mapping.by_reference {
+                format!("&{}", mapping.rust_type).into()
             } else {
-                "fiberplane_models::names::Name".into()
-            }
-        }
-        Some("int32") => "i32".into(),
-        Some("int64") => "i64".into(),
-        Some("float") => "f32".into(),
-        Some("double") => "f64".into(),
-        Some("byte") => "Vec<u8>".into(), // TODO: Deserialize from Base64
-        Some("binary") => "Vec<u8>".into(),
-        Some("date") | Some("date-time") => "fiberplane_models::timestamps::Timestamp".into(),
-        Some("password") => "secrecy::SecretString".into(),
-        Some(_) | None => {
-            if let Some(SingleOrVec::Single(instance_type)) = &instance_type {
-                match **instance_type {
-                    InstanceType::Null => "()".into(),
-                    InstanceType::Boolean => "bool".into(),
-                    InstanceType::Object => "std::collections::HashMap<String, String>".into(),
-                    InstanceType::Array => {
-                        if let Some(reference) = reference {
-                            format!("Vec<{}>", reference_name_to_models_path(reference)).into()
-                        } else {
-                            "Vec<serde_json::Value>".into()
-                        }
-                    }
-                    InstanceType::Number => "i64".into(),
-                    InstanceType::String => {
-                        if as_ref {
-                            "&str".into()
-                        } else {
-                            "String".into()
+                mapping.rust_type.into()
+            });
+        }
+
+        // without a mapping, the extension is used as the path of the type
+        if let Some(rust_type) = rust_type_extension(schema) {
+            return Ok(rust_type.into());
+        }
+
+        let instance_type = single_instance_type(schema);
+        let reference = schema.reference.as_deref();
+
+        Ok(match schema.format.as_deref() {
+            Some("int8") => signed_or_unsigned(schema, "i8", "u8").into(),
+            Some("int16") => signed_or_unsigned(schema, "i16", "u16").into(),
+            Some("int32") => signed_or_unsigned(schema, "i32", "u32").into(),
+            Some("int64") => signed_or_unsigned(schema, "i64", "u64").into(),
+            Some("uint8") => "u8".into(),
+            Some("uint16") => "u16".into(),
+            Some("uint32") => "u32".into(),
+            Some("uint64") => "u64".into(),
+            Some("float") => "f32".into(),
+            Some("double") => "f64".into(),
+            Some("uuid") => "uuid::Uuid".into(),
+            Some("uri") | Some("url") => "url::Url".into(),
+            Some("ip") => "std::net::IpAddr".into(),
+            Some("ipv4") => "std::net::Ipv4Addr".into(),
+            Some("ipv6") => "std::net::Ipv6Addr".into(),
+            // `email`, `hostname` and `duration` (ISO 8601) don't have a type everyone agrees on,
+            // so they're kept as strings unless a mapping is configured for them
+            Some("byte") => {
+                self.base64.set(true);
+                "types::Base64".into()
+            }
+            Some("binary") => "Vec<u8>".into(),
+            Some(_) | None => {
+                if let Some(instance_type) = instance_type {
+                    match instance_type {
+                        InstanceType::Null => "()".into(),
+                        InstanceType::Boolean => "bool".into(),
+                        InstanceType::Object => self.map_object(schema, name)?.into(),
+                        InstanceType::Array => self.map_array(schema, name)?.into(),
+                        InstanceType::Number => "f64".into(),
+                        InstanceType::String => {
+                            if as_ref {
+                                "&str".into()
+                            } else {
+                                "String".into()
+                            }
                         }
+                        InstanceType::Integer => signed_or_unsigned(schema, "i32", "u32").into(),
                     }
-                    InstanceType::Integer => "i32".into(),
+                } else if let Some(reference) = reference {
+                    reference_name_to_models_path(reference).into()
+                } else {
+                    bail!("Failed to write field. Unsupported instance_type and reference is None");
+                }
+            }
+        })
+    }
+
+    /// Maps a nested schema, returning the type and whether the value may be `null`.
+    fn map_schema(&self, schema: &Schema, name: &str) -> Result<(String, bool)> {
+        match schema {
+            Schema::Object(object) => Ok((
+                self.map_type(object, name, false)?.into_owned(),
+                is_nullable(object),
+            )),
+            Schema::Bool(_) => Ok(("serde_json::Value".to_owned(), false)),
+        }
+    }
+
+    /// Maps a nested schema, wrapping the type in an `Option` if it's nullable.
+    fn map_nested(&self, schema: &Schema, name: &str) -> Result<String> {
+        let (type_, nullable) = self.map_schema(schema, name)?;
+
+        Ok(if nullable {
+            format!("Option<{type_}>")
+        } else {
+            type_
+        })
+    }
+
+    fn map_array(&self, schema: &SchemaObject, name: &str) -> Result<String> {
+        let items = schema.array.as_ref().and_then(|array| array.items.as_ref());
+
+        Ok(match items {
+            Some(SingleOrVec::Single(items)) => {
+                format!("Vec<{}>", self.map_nested(items, &format!("{name}Item"))?)
+            }
+            // tuples and arrays without items can hold anything
+            Some(SingleOrVec::Vec(_)) | None => "Vec<serde_json::Value>".to_owned(),
+        })
+    }
+
+    fn map_object(&self, schema: &SchemaObject, name: &str) -> Result<String> {
+        let object = match &schema.object {
+            Some(object) => object,
+            None => return Ok("std::collections::HashMap<String, serde_json::Value>".to_owned()),
+        };
+
+        if object.properties.is_empty() {
+            return Ok(match object.additional_properties.as_deref() {
+                Some(schema @ Schema::Object(_)) => format!(
+                    "std::collections::HashMap<String, {}>",
+                    self.map_nested(schema, &format!("{name}Value"))?
+                ),
+                // without a schema for the values, they can be anything
+                Some(Schema::Bool(_)) | None => {
+                    "std::collections::HashMap<String, serde_json::Value>".to_owned()
                 }
-            } else if let Some(reference) = reference {
-                reference_name_to_models_path(reference).into()
+            });
+        }
+
+        // properties that are valid field names already keep their name, so only the renamed
+        // ones get a number appended on collisions, regardless of the order of the properties
+        let (exact, renamed): (Vec<_>, Vec<_>) = object
+            .properties
+            .keys()
+            .partition(|property| snake_case(property) == **property);
+        let mut taken = BTreeSet::new();
+        let field_names: BTreeMap<_, _> = exact
+            .into_iter()
+            .chain(renamed)
+            .map(|property| (property, unique_name(snake_case(property), &mut taken)))
+            .collect();
+
+        let mut fields = Vec::new();
+
+        for (property, schema) in &object.properties {
+            let field = field_names[property].clone();
+            let (type_, nullable) =
+                self.map_schema(schema, &format!("{name}{}", pascal_case(property)))?;
+            let type_ = parse_type(&type_)?;
+
+            let docs = match schema {
+                Schema::Object(object) => object
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.description.as_deref())
+                    .map(doc),
+                Schema::Bool(_) => None,
+            };
+
+            let rename = if *property != field {
+                Some(quote! { #[serde(rename = #property)] })
             } else {
-                bail!("Failed to write field. Unsupported instance_type and reference is None");
+                None
+            };
+
+            let ident = ident(&field);
+
+            fields.push(match (object.required.contains(property), nullable) {
+                (true, false) => quote! { #docs #rename pub #ident: #type_ },
+                (true, true) => quote! { #docs #rename pub #ident: Option<#type_> },
+                (false, false) => quote! {
+                    #docs
+                    #rename
+                    #[serde(default, skip_serializing_if = "Option::is_none")]
+                    pub #ident: Option<#type_>
+                },
+                // absent and `null` need to be distinguished, for example for partial updates
+                (false, true) => {
+                    self.nullable.set(true);
+
+                    quote! {
+                        #docs
+                        #rename
+                        #[serde(default, skip_serializing_if = "Nullable::is_absent")]
+                        pub #ident: types::Nullable<#type_>
+                    }
+                }
+            });
+        }
+
+        let docs = schema
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.as_deref())
+            .map(doc);
+
+        let mut structs = self.structs.borrow_mut();
+
+        // different schemas may end up with the same name, so number them
+        let mut unique_name = name.to_owned();
+        let mut counter = 1;
+
+        loop {
+            let ident = ident(&unique_name);
+            let definition = quote! {
+                #docs
+                #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+                pub struct #ident {
+                    #(#fields,)*
+                }
+            };
+
+            match structs.get(&unique_name) {
+                Some(existing) if existing.to_string() != definition.to_string() => {
+                    counter += 1;
+                    unique_name = format!("{name}{counter}");
+                }
+                _ => {
+                    structs.insert(unique_name.clone(), definition);
+                    break;
+                }
             }
         }
-    })
+
+        Ok(format!("types::{unique_name}"))
+    }
+
+    /// Generates the `types` module containing the structs for all inline object schemas and
+    /// the helper types used by them, if there are any.
+    pub(crate) fn generate_types_module(&self) -> Result<Option<String>> {
+        if !self.has_types() {
+            return Ok(None);
+        }
+
+        let structs = self.structs.borrow();
+        let structs = structs.values();
+        let base64 = self.base64.get().then(generate_base64);
+        let nullable = self.nullable.get().then(generate_nullable);
+
+        format_file(quote! {
+            #![allow(unused_imports)]
+
+            use super::models;
+            use super::types;
+
+            #(#structs)*
+            #base64
+            #nullable
+        })
+        .map(Some)
+    }
+
+    /// Whether the `types` module is needed.
+    pub(crate) fn has_types(&self) -> bool {
+        !self.structs.borrow().is_empty() || self.base64.get() || self.nullable.get()
+    }
+}
+
+/// Generates the `Nullable` type used for properties that are both optional and nullable.
+fn generate_nullable() -> TokenStream {
+    quote! {
+        /// The value of a property that is both optional and nullable.
+        ///
+        /// Unlike an `Option`, this distinguishes between the property being absent and being
+        /// explicitly set to `null`, which partial updates use to clear a value. Absent
+        /// properties are not serialized.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Nullable<T> {
+            Absent,
+            Null,
+            Value(T),
+        }
+
+        impl<T> Nullable<T> {
+            pub fn is_absent(&self) -> bool {
+                matches!(self, Nullable::Absent)
+            }
+
+            pub fn is_null(&self) -> bool {
+                matches!(self, Nullable::Null)
+            }
+
+            pub fn as_ref(&self) -> Nullable<&T> {
+                match self {
+                    Nullable::Absent => Nullable::Absent,
+                    Nullable::Null => Nullable::Null,
+                    Nullable::Value(value) => Nullable::Value(value),
+                }
+            }
+
+            /// Returns the value, treating absent and `null` the same.
+            pub fn into_option(self) -> Option<T> {
+                match self {
+                    Nullable::Absent | Nullable::Null => None,
+                    Nullable::Value(value) => Some(value),
+                }
+            }
+        }
+
+        impl<T> Default for Nullable<T> {
+            fn default() -> Self {
+                Nullable::Absent
+            }
+        }
+
+        impl<T> From<Option<T>> for Nullable<T> {
+            fn from(value: Option<T>) -> Self {
+                match value {
+                    Some(value) => Nullable::Value(value),
+                    None => Nullable::Null,
+                }
+            }
+        }
+
+        impl<T: serde::Serialize> serde::Serialize for Nullable<T> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    Nullable::Absent | Nullable::Null => serializer.serialize_none(),
+                    Nullable::Value(value) => serializer.serialize_some(value),
+                }
+            }
+        }
+
+        impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Nullable<T> {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Into::into)
+            }
+        }
+    }
+}
+
+/// Generates the `Base64` newtype used for `format: byte`.
+fn generate_base64() -> TokenStream {
+    quote! {
+        /// Binary data, which is encoded as a base64 string.
+        ///
+        /// Standard base64 is used for encoding, while both the standard and the URL-safe
+        /// alphabet are accepted when decoding, with or without padding.
+        #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+        pub struct Base64(pub Vec<u8>);
+
+        impl Base64 {
+            const STANDARD: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+                &base64::alphabet::STANDARD,
+                base64::engine::GeneralPurposeConfig::new()
+                    .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+            );
+            const URL_SAFE: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+                &base64::alphabet::URL_SAFE,
+                base64::engine::GeneralPurposeConfig::new()
+                    .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+            );
+
+            pub fn encode(&self) -> String {
+                base64::Engine::encode(&Self::STANDARD, &self.0)
+            }
+
+            pub fn decode(input: &str) -> Result<Self, base64::DecodeError> {
+                let engine = if input.contains(|c| c == '-' || c == '_') {
+                    &Self::URL_SAFE
+                } else {
+                    &Self::STANDARD
+                };
+
+                base64::Engine::decode(engine, input).map(Self)
+            }
+        }
+
+        impl From<Vec<u8>> for Base64 {
+            fn from(bytes: Vec<u8>) -> Self {
+                Self(bytes)
+            }
+        }
+
+        impl From<Base64> for Vec<u8> {
+            fn from(base64: Base64) -> Self {
+                base64.0
+            }
+        }
+
+        impl std::ops::Deref for Base64 {
+            type Target = [u8];
+
+            fn deref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl std::fmt::Display for Base64 {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.encode())
+            }
+        }
+
+        impl std::str::FromStr for Base64 {
+            type Err = base64::DecodeError;
+
+            fn from_str(input: &str) -> Result<Self, Self::Err> {
+                Self::decode(input)
+            }
+        }
+
+        impl serde::Serialize for Base64 {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.encode())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Base64 {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let input = <String as serde::Deserialize>::deserialize(deserializer)?;
+                Self::decode(&input).map_err(serde::de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Whether `null` is allowed, either through `nullable: true` or a list of types including it.
+pub(crate) fn is_nullable(schema: &SchemaObject) -> bool {
+    let listed = matches!(
+        &schema.instance_type,
+        Some(SingleOrVec::Vec(types)) if types.contains(&InstanceType::Null)
+    );
+
+    listed
+        || schema
+            .extensions
+            .get("nullable")
+            .and_then(|nullable| nullable.as_bool())
+            == Some(true)
+}
+
+/// Returns the instance type of the schema, ignoring `null` if it's listed with exactly one
+/// other type.
+fn single_instance_type(schema: &SchemaObject) -> Option<InstanceType> {
+    match schema.instance_type.as_ref()? {
+        SingleOrVec::Single(instance_type) => Some(**instance_type),
+        SingleOrVec::Vec(types) => {
+            let mut types = types.iter().filter(|type_| **type_ != InstanceType::Null);
+
+            match (types.next(), types.next()) {
+                (Some(instance_type), None) => Some(*instance_type),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Picks the unsigned type if the schema doesn't allow negative numbers.
+fn signed_or_unsigned(
+    schema: &SchemaObject,
+    signed: &'static str,
+    unsigned: &'static str,
+) -> &'static str {
+    match schema.number.as_ref().and_then(|number| number.minimum) {
+        Some(minimum) if minimum >= 0.0 => unsigned,
+        _ => signed,
+    }
 }
 
 pub(crate) fn reference_name_to_models_path(input: &str) -> String {
     if let Some((_, reference_name)) = input.rsplit_once('/') {
-        format!("models::{}", reference_name.to_case(Case::Pascal))
+        format!("models::{}", pascal_case(reference_name))
     } else {
-        format!("models::{}", input.to_case(Case::Pascal))
+        format!("models::{}", pascal_case(input))
     }
 }
 
@@ -156,6 +605,7 @@ pub(crate) fn resolve<'a>(
 
 #[derive(Debug, Clone)]
 pub(crate) enum ResolvedReference<'a> {
+    #[allow(dead_code)]
     Schema(Cow<'a, SchemaObject>),
     Parameter(Cow<'a, Parameter>),
     Responses(Cow<'a, Response>),
@@ -208,3 +658,174 @@ pub(crate) fn resolve_reference<'a>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(schema: &str) -> String {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let schema: SchemaObject = serde_yaml::from_str(schema).unwrap();
+
+        mapper
+            .map_type(&schema, "Test", false)
+            .unwrap()
+            .into_owned()
+    }
+
+    #[test]
+    fn integer_formats() {
+        assert_eq!(map("{ type: integer }"), "i32");
+        assert_eq!(map("{ type: integer, format: int8 }"), "i8");
+        assert_eq!(map("{ type: integer, format: int16 }"), "i16");
+        assert_eq!(map("{ type: integer, format: int32 }"), "i32");
+        assert_eq!(map("{ type: integer, format: int64 }"), "i64");
+        assert_eq!(map("{ type: integer, format: uint8 }"), "u8");
+        assert_eq!(map("{ type: integer, format: uint16 }"), "u16");
+        assert_eq!(map("{ type: integer, format: uint32 }"), "u32");
+        assert_eq!(map("{ type: integer, format: uint64 }"), "u64");
+    }
+
+    #[test]
+    fn non_negative_integers_are_unsigned() {
+        assert_eq!(map("{ type: integer, minimum: 0 }"), "u32");
+        assert_eq!(map("{ type: integer, format: int64, minimum: 1 }"), "u64");
+        assert_eq!(map("{ type: integer, format: int8, minimum: -1 }"), "i8");
+        assert_eq!(map("{ type: integer, format: int16, maximum: 10 }"), "i16");
+    }
+
+    #[test]
+    fn other_formats() {
+        assert_eq!(map("{ type: number }"), "f64");
+        assert_eq!(map("{ type: number, format: float }"), "f32");
+        assert_eq!(map("{ type: number, format: double }"), "f64");
+        assert_eq!(map("{ type: string, format: uuid }"), "uuid::Uuid");
+        assert_eq!(map("{ type: string, format: uri }"), "url::Url");
+        assert_eq!(map("{ type: string, format: ipv4 }"), "std::net::Ipv4Addr");
+        assert_eq!(map("{ type: string, format: byte }"), "types::Base64");
+        assert_eq!(map("{ type: string, format: binary }"), "Vec<u8>");
+        // formats without a type of their own are kept as strings
+        assert_eq!(map("{ type: string, format: email }"), "String");
+        assert_eq!(map("{ type: string, format: hostname }"), "String");
+        assert_eq!(map("{ type: string, format: duration }"), "String");
+    }
+
+    #[test]
+    fn base64_type_is_only_generated_when_used() {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let schema: SchemaObject = serde_yaml::from_str("{ type: string }").unwrap();
+        mapper.map_type(&schema, "Test", false).unwrap();
+
+        assert!(mapper.generate_types_module().unwrap().is_none());
+
+        let schema: SchemaObject =
+            serde_yaml::from_str("{ type: array, items: { type: string, format: byte } }").unwrap();
+
+        assert_eq!(
+            mapper.map_type(&schema, "Test", false).unwrap(),
+            "Vec<types::Base64>"
+        );
+        assert!(mapper
+            .generate_types_module()
+            .unwrap()
+            .unwrap()
+            .contains("pub struct Base64(pub Vec<u8>);"));
+    }
+
+    #[test]
+    fn optional_and_nullable_properties() {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let schema: SchemaObject = serde_yaml::from_str(
+            r#"
+type: object
+required: [title, parent]
+properties:
+  title: { type: string }
+  parent: { type: string, nullable: true }
+  color: { type: string }
+  description: { type: string, nullable: true }
+  labels: { type: array, items: { type: string, nullable: true } }
+"#,
+        )
+        .unwrap();
+        mapper.map_type(&schema, "Update", false).unwrap();
+
+        let definition = mapper.structs.borrow()["Update"]
+            .to_string()
+            .replace(char::is_whitespace, "");
+        assert!(definition.contains("pubtitle:String,"), "{definition}");
+        assert!(
+            definition.contains("pubparent:Option<String>,"),
+            "{definition}"
+        );
+        assert!(definition.contains(
+            r#"#[serde(default,skip_serializing_if="Option::is_none")]pubcolor:Option<String>,"#
+        ));
+        assert!(definition.contains(
+            r#"#[serde(default,skip_serializing_if="Nullable::is_absent")]pubdescription:types::Nullable<String>,"#
+        ));
+        assert!(definition.contains("publabels:Option<Vec<Option<String>>>"));
+        assert!(mapper
+            .generate_types_module()
+            .unwrap()
+            .unwrap()
+            .contains("pub enum Nullable<T> {"));
+    }
+
+    #[test]
+    fn maps_without_value_schema_hold_any_value() {
+        assert_eq!(
+            map("{ type: object }"),
+            "std::collections::HashMap<String, serde_json::Value>"
+        );
+        assert_eq!(
+            map("{ type: object, additionalProperties: true }"),
+            "std::collections::HashMap<String, serde_json::Value>"
+        );
+        assert_eq!(
+            map("{ type: object, additionalProperties: { type: integer } }"),
+            "std::collections::HashMap<String, i32>"
+        );
+    }
+
+    #[test]
+    fn exact_field_names_are_kept_on_collisions() {
+        let config = Config::new("api");
+        let mapper = TypeMapper::new(&config);
+        let schema: SchemaObject = serde_yaml::from_str(
+            "{ type: object, required: [title], properties: { Title: { type: string }, title: { type: string } } }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mapper.map_type(&schema, "Test", false).unwrap(),
+            "types::Test"
+        );
+
+        let definition = mapper.structs.borrow()["Test"].to_string();
+        assert!(definition.contains("pub title : String"), "{definition}");
+        assert!(
+            definition.contains("# [serde (rename = \"Title\")] # [serde (default , skip_serializing_if = \"Option::is_none\")] pub title2 : Option < String >"),
+            "{definition}"
+        );
+    }
+
+    #[test]
+    fn configured_mappings_take_precedence() {
+        let mut config = Config::new("api");
+        config
+            .type_mappings
+            .insert("int64".to_owned(), TypeMapping::new("num_bigint::BigInt"));
+        let mapper = TypeMapper::new(&config);
+        let schema: SchemaObject =
+            serde_yaml::from_str("{ type: integer, format: int64, minimum: 0 }").unwrap();
+
+        assert_eq!(
+            mapper.map_type(&schema, "Test", false).unwrap(),
+            "num_bigint::BigInt"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! The `lib.rs` of the crate including the generated client as a module, with tests for the
+//! behavior of the generated client that doesn't need a server.
+
+include!("api.rs");
+
+#[cfg(test)]
+mod tests {
+    use super::api::clients::{ApiClient, RequestOptions};
+    use reqwest::header::{HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT};
+    use reqwest::Method;
+    use std::time::Duration;
+
+    fn client() -> ApiClient {
+        ApiClient::builder("https://api.example.com".parse().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn request_options_are_applied() {
+        let options = RequestOptions::new()
+            .timeout(Duration::from_secs(1))
+            .header(ACCEPT, HeaderValue::from_static("text/plain"))
+            .idempotency_key("retry-1");
+        let request = client()
+            .with_options(options)
+            .request(Method::POST, "/items")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.timeout(), Some(&Duration::from_secs(1)));
+        assert_eq!(request.headers()[ACCEPT], "text/plain");
+        assert_eq!(request.headers()["Idempotency-Key"], "retry-1");
+    }
+
+    #[test]
+    fn requests_without_options_are_left_alone() {
+        let request = client()
+            .request(Method::GET, "/items")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.timeout(), None);
+        assert!(request.headers().is_empty());
+    }
+
+    #[test]
+    fn operations_with_their_own_servers_keep_the_base_path() {
+        let client = client();
+        let url = |base_url| {
+            client
+                .request_with_base(base_url, Method::HEAD, "/files/1")
+                .unwrap()
+                .build()
+                .unwrap()
+                .url()
+                .to_string()
+        };
+
+        assert_eq!(
+            url("https://files.example.com/v1"),
+            "https://files.example.com/v1/files/1"
+        );
+        // relative to the server of the client
+        assert_eq!(url("/debug"), "https://api.example.com/debug/files/1");
+    }
+
+    #[test]
+    fn builder_options_configure_the_client() {
+        let client = ApiClient::builder("https://api.example.com".parse().unwrap())
+            .request_timeout(Some(Duration::from_secs(30)))
+            .proxy(reqwest::Proxy::all("http://proxy.example.com:8080").unwrap())
+            .build()
+            .unwrap();
+        let client = format!("{:?}", client.client);
+
+        assert!(client.contains("timeout: 30s"), "{client}");
+        assert!(client.contains("http://proxy.example.com:8080"), "{client}");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn timeout_sets_the_connect_timeout() {
+        let builder = ApiClient::builder("https://api.example.com".parse().unwrap())
+            .timeout(Some(Duration::from_secs(1)));
+        let builder = format!("{builder:?}");
+
+        assert!(builder.contains("request_timeout: None"), "{builder}");
+        assert!(builder.contains("connect_timeout: Some(1s)"), "{builder}");
+    }
+
+    #[test]
+    fn debug_output_hides_the_token() {
+        let builder = ApiClient::builder("https://api.example.com".parse().unwrap())
+            .bearer_token(Some("secret"));
+        let debug = format!("{builder:?}");
+        assert!(debug.contains("bearer_token: Some(\"Sensitive\")"), "{debug}");
+        assert!(!debug.contains("secret"), "{debug}");
+
+        let debug = format!("{:?}", builder.build().unwrap());
+        assert!(debug.contains("\"authorization\": Sensitive"), "{debug}");
+        assert!(!debug.contains("secret"), "{debug}");
+    }
+
+    #[test]
+    fn shared_clients_keep_their_user_agent() {
+        let shared = reqwest::Client::builder()
+            .user_agent("app/1.0")
+            .build()
+            .unwrap();
+        let client = ApiClient::builder("https://api.example.com".parse().unwrap())
+            .bearer_token(Some("secret"))
+            .client(shared.clone())
+            .build()
+            .unwrap();
+        let request = client
+            .request(Method::GET, "/items")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer secret");
+        assert!(request.headers().get(USER_AGENT).is_none());
+        // clones share the client, including its configuration
+        assert!(format!("{:?}", client.clone().client).contains("app/1.0"));
+
+        let client = ApiClient::builder("https://api.example.com".parse().unwrap())
+            .user_agent(Some("api/2.0"))
+            .client(shared)
+            .build()
+            .unwrap();
+        let request = client
+            .request(Method::GET, "/items")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()[USER_AGENT], "api/2.0");
+    }
+}
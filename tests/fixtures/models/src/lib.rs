@@ -0,0 +1,16 @@
+//! Hand written models for the schemas of `openapi.yaml`, like the ones of a real models crate.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Item {
+    pub id: String,
+}
+
+/// Unlike the structs generated for inline schemas, the optional cursor is a plain `Option`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ItemPage {
+    pub data: Vec<Item>,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
+}
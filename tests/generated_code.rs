@@ -0,0 +1,99 @@
+//! Builds and tests the code generated for `tests/fixtures/openapi.yaml`, so changes to the
+//! generator that produce code which doesn't compile are caught. The tests of the generated code
+//! check that the examples of the document match the generated types.
+//!
+//! The schemas of the document are taken from the `models` crate next to it, like they would be
+//! from a real models crate. The client is also embedded into another crate as a single module,
+//! like `build` does, whose `lib.rs` (`embedded.rs`) tests the generated client.
+
+use fp_openapi_rust_gen::{generate, load_document, Config, Profile, Target};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use toml_edit::Document;
+
+#[test]
+fn generated_code_compiles() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let workspace = Path::new(env!("CARGO_TARGET_TMPDIR")).join("generated");
+    let document = load_document(&fixtures.join("openapi.yaml")).unwrap();
+
+    let targets = [
+        ("client", Target::Client),
+        ("server", Target::Server),
+        ("cli", Target::Cli),
+    ];
+
+    for (name, target) in targets {
+        let config = Config {
+            target,
+            profile: Profile::Generic,
+            models: vec!["models::*".to_owned()],
+            ..Config::new(name)
+        };
+
+        let models = format!(
+            "\n[dependencies.models]\npath = {:?}\n",
+            fixtures.join("models")
+        );
+        let mut files = generate(&document, &config).unwrap();
+        files.manifest.push_str(&models);
+
+        // prettyplease leaves the arguments of macros as they're quoted, so expressions passed to
+        // them come out with spaces like `& item ?`
+        for (file, source) in &files.sources {
+            assert!(
+                !source.contains(" ?"),
+                "Unformatted macro arguments in {name}/src/{}",
+                file.display()
+            );
+        }
+
+        let path = workspace.join(name);
+        let _ = fs::remove_dir_all(&path);
+        files.write_crate(&path).unwrap();
+
+        if target == Target::Client {
+            // the including crate enables TLS on `reqwest` itself, without the features of the
+            // generated crate
+            let manifest = files
+                .merge_dependencies(&format!(
+                    "[package]\nname = \"embedded\"\nversion = \"0.1.0\"\nedition = \"2021\"\n{models}"
+                ))
+                .unwrap();
+            let mut manifest: Document = manifest.parse().unwrap();
+            manifest.remove("features");
+            manifest["dependencies"]["reqwest"]["features"]
+                .as_array_mut()
+                .unwrap()
+                .push("rustls-tls");
+
+            let path = workspace.join("embedded");
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("src")).unwrap();
+            fs::write(path.join("Cargo.toml"), manifest.to_string()).unwrap();
+            fs::write(path.join("src/api.rs"), files.to_module("api").unwrap()).unwrap();
+            fs::copy(fixtures.join("embedded.rs"), path.join("src/lib.rs")).unwrap();
+        }
+    }
+
+    fs::write(
+        workspace.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"client\", \"server\", \"cli\", \"embedded\"]\nresolver = \"2\"\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO"))
+        .args(["test", "--workspace"])
+        .current_dir(&workspace)
+        .env("RUSTFLAGS", "-D warnings")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "Generated code does not compile or its tests fail:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}